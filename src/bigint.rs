@@ -0,0 +1,380 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Mul, Neg, Sub};
+
+// arbitrary precision integer, the magnitude is kept in base 2^32 with the
+// least significant digit first and no leading zeros, so zero has no digits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, digits: Vec<u32>) -> BigInt {
+        let digits = trim(digits);
+        BigInt {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    // digits of any radix up to 36 with an optional minus sign
+    pub fn parse_radix(text: &str, radix: u32) -> Option<BigInt> {
+        let (negative, number) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if number.is_empty() {
+            return None;
+        }
+        let mut digits: Vec<u32> = Vec::new();
+        for c in number.chars() {
+            mul_small_add(&mut digits, radix, c.to_digit(radix)?);
+        }
+        Some(BigInt::new(negative, digits))
+    }
+
+    // the number as an int, None when it doesn't fit in one
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let magnitude = self.digits.iter().rev().fold(0u64, |acc, d| acc << 32 | *d as u64);
+        if self.negative {
+            0i64.checked_sub_unsigned(magnitude)
+        } else {
+            i64::try_from(magnitude).ok()
+        }
+    }
+
+    // the bits of the magnitude, none for zero
+    pub fn bits(&self) -> u64 {
+        match self.digits.last() {
+            Some(top) => self.digits.len() as u64 * 32 - top.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.digits.iter().rev().fold(0.0, |acc, d| acc * 4294967296.0 + *d as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    // truncating division like the one of ints, None when dividing by zero
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (quotient, rest) = div_rem_digits(&self.digits, &other.digits);
+        Some((
+            BigInt::new(self.negative != other.negative, quotient),
+            BigInt::new(self.negative, rest),
+        ))
+    }
+
+    // floor division, the rest has the sign of the divisor, None when dividing by zero
+    pub fn div_mod_floor(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        let (quotient, rest) = self.div_rem(other)?;
+        if !rest.is_zero() && rest.negative != other.negative {
+            Some((&quotient - &BigInt::from(1), &rest + other))
+        } else {
+            Some((quotient, rest))
+        }
+    }
+
+    // square and multiply
+    pub fn pow(&self, mut exponent: u32) -> BigInt {
+        let mut result = BigInt::from(1);
+        let mut base = self.clone();
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = &result * &base;
+            }
+            base = &base * &base;
+            exponent >>= 1;
+        }
+        result
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(n: i64) -> BigInt {
+        let magnitude = n.unsigned_abs();
+        BigInt::new(n < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_digits(&self.digits, &other.digits));
+        }
+        match cmp_digits(&self.digits, &other.digits) {
+            Ordering::Less => BigInt::new(other.negative, sub_digits(&other.digits, &self.digits)),
+            _ => BigInt::new(self.negative, sub_digits(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut result = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, a) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.digits.iter().enumerate() {
+                let sum = result[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                result[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            result[i + other.digits.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, result)
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.digits.clone())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_digits(&self.digits, &other.digits),
+            (true, true) => cmp_digits(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // split into groups of nine decimal digits, the lowest group first
+        let mut groups: Vec<u32> = Vec::new();
+        let mut rest = self.digits.clone();
+        while !rest.is_empty() {
+            let (quotient, group) = div_small(&rest, 1_000_000_000);
+            groups.push(group);
+            rest = quotient;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut groups = groups.iter().rev();
+        if let Some(first) = groups.next() {
+            write!(f, "{}", first)?;
+        }
+        for group in groups {
+            write!(f, "{:09}", group)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_digits(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result: Vec<u32> = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0u64;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    trim(result)
+}
+
+// a has to be at least as big as b
+fn sub_digits(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result: Vec<u32> = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let mut diff = *digit as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << 32;
+            borrow = 1;
+        }
+        result.push(diff as u32);
+    }
+    trim(result)
+}
+
+fn mul_small_add(digits: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for digit in digits.iter_mut() {
+        let product = *digit as u64 * factor as u64 + carry;
+        *digit = product as u32;
+        carry = product >> 32;
+    }
+    if carry > 0 {
+        digits.push(carry as u32);
+    }
+}
+
+fn div_small(digits: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; digits.len()];
+    let mut rest = 0u64;
+    for (i, digit) in digits.iter().enumerate().rev() {
+        let current = (rest << 32) | *digit as u64;
+        quotient[i] = (current / divisor as u64) as u32;
+        rest = current % divisor as u64;
+    }
+    (trim(quotient), rest as u32)
+}
+
+// long division one bit at a time, the divisor can't be zero
+fn div_rem_digits(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if b.len() == 1 {
+        let (quotient, rest) = div_small(a, b[0]);
+        return (quotient, trim(vec![rest]));
+    }
+    let mut quotient = vec![0u32; a.len()];
+    let mut rest: Vec<u32> = Vec::new();
+    for bit in (0..a.len() * 32).rev() {
+        mul_small_add(&mut rest, 2, (a[bit / 32] >> (bit % 32)) & 1);
+        if cmp_digits(&rest, b) != Ordering::Less {
+            rest = sub_digits(&rest, b);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(quotient), rest)
+}
+
+fn trim(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(text: &str) -> BigInt {
+        BigInt::parse_radix(text, 10).unwrap()
+    }
+
+    #[test]
+    fn parses_and_prints() {
+        assert_eq!(big("0").to_string(), "0");
+        assert_eq!(big("-0").to_string(), "0");
+        assert_eq!(big("123456789012345678901234567890").to_string(), "123456789012345678901234567890");
+        assert_eq!(big("-1000000000").to_string(), "-1000000000");
+        assert_eq!(BigInt::parse_radix("ff", 16), Some(BigInt::from(255)));
+        assert_eq!(BigInt::parse_radix("-101", 2), Some(BigInt::from(-5)));
+        assert_eq!(BigInt::parse_radix("", 10), None);
+        assert_eq!(BigInt::parse_radix("-", 10), None);
+        assert_eq!(BigInt::parse_radix("12a", 10), None);
+    }
+
+    #[test]
+    fn converts_from_and_to_ints() {
+        for n in [0, 1, -1, 4294967296, i64::MAX, i64::MIN] {
+            assert_eq!(BigInt::from(n).to_i64(), Some(n));
+            assert_eq!(BigInt::from(n).to_string(), n.to_string());
+        }
+        assert_eq!(big("9223372036854775808").to_i64(), None);
+        assert_eq!(big("-9223372036854775809").to_i64(), None);
+        assert_eq!(big("-12345678901234567890").to_f64(), -12345678901234567890.0);
+    }
+
+    #[test]
+    fn adds_and_subtracts_across_signs() {
+        let a = big("99999999999999999999");
+        let b = big("-99999999999999999998");
+        assert_eq!(&a + &b, BigInt::from(1));
+        assert_eq!(&b + &a, BigInt::from(1));
+        assert_eq!(&a - &a, BigInt::from(0));
+        assert_eq!(&b - &a, big("-199999999999999999997"));
+        assert_eq!(&BigInt::from(u32::MAX as i64) + &BigInt::from(1), BigInt::from(4294967296));
+        assert_eq!(-&BigInt::from(0), BigInt::from(0));
+    }
+
+    #[test]
+    fn multiplies_and_raises() {
+        let a = big("123456789012345678901234567890");
+        assert_eq!((&a * &BigInt::from(-2)).to_string(), "-246913578024691357802469135780");
+        assert_eq!(&a * &BigInt::from(0), BigInt::from(0));
+        assert_eq!(BigInt::from(2).pow(100).to_string(), "1267650600228229401496703205376");
+        assert_eq!(BigInt::from(-3).pow(3), BigInt::from(-27));
+        assert_eq!(BigInt::from(0).bits(), 0);
+        assert_eq!(BigInt::from(-1).bits(), 1);
+        assert_eq!(BigInt::from(2).pow(100).bits(), 101);
+        assert_eq!(BigInt::from(7).pow(0), BigInt::from(1));
+    }
+
+    #[test]
+    fn divides_like_ints() {
+        // a b, the truncated quotient and rest, then the floored ones
+        let cases = [
+            (7, 2, (3, 1), (3, 1)),
+            (-7, 2, (-3, -1), (-4, 1)),
+            (7, -2, (-3, 1), (-4, -1)),
+            (-7, -2, (3, -1), (3, -1)),
+            (6, -3, (-2, 0), (-2, 0)),
+            (0, 5, (0, 0), (0, 0)),
+        ];
+        for (a, b, truncated, floored) in cases {
+            let (a, b) = (BigInt::from(a), BigInt::from(b));
+            let (quotient, rest) = a.div_rem(&b).unwrap();
+            assert_eq!((quotient, rest), (BigInt::from(truncated.0), BigInt::from(truncated.1)));
+            let (quotient, rest) = a.div_mod_floor(&b).unwrap();
+            assert_eq!((quotient, rest), (BigInt::from(floored.0), BigInt::from(floored.1)));
+        }
+        assert_eq!(BigInt::from(1).div_rem(&BigInt::from(0)), None);
+        assert_eq!(BigInt::from(1).div_mod_floor(&BigInt::from(0)), None);
+    }
+
+    #[test]
+    fn divides_long_numbers() {
+        let a = big("1267650600228229401496703205377");
+        let (quotient, rest) = a.div_rem(&big("18446744073709551616")).unwrap();
+        assert_eq!(quotient, big("68719476736"));
+        assert_eq!(rest, BigInt::from(1));
+        let (quotient, rest) = (-&a).div_mod_floor(&big("18446744073709551616")).unwrap();
+        assert_eq!(quotient, big("-68719476737"));
+        assert_eq!(rest, big("18446744073709551615"));
+    }
+
+    #[test]
+    fn orders_by_value() {
+        assert!(big("-100000000000000000000") < BigInt::from(-1));
+        assert!(BigInt::from(-1) < BigInt::from(0));
+        assert!(BigInt::from(0) < big("100000000000000000000"));
+        assert!(big("-100000000000000000000") < big("-99999999999999999999"));
+    }
+}
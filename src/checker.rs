@@ -0,0 +1,1325 @@
+use crate::compiler::{struct_types, typed};
+use crate::error::{undefined_word, ErrorKind, NumenError};
+use crate::head::{Function, Object, Pos, TokId, Value, BUILTINS, CONSTANTS, CORE, GLOBAL, MAIN};
+use std::collections::{HashMap, HashSet};
+use std::slice::Iter;
+
+// passes over the program to infer what its functions leave on the stack,
+// each one sees one call further
+const PASSES: usize = 8;
+
+const NUMBERS: [TokId; 3] = [TokId::INT, TokId::BIGINT, TokId::FLOAT];
+const ADDABLE: [TokId; 5] = [TokId::INT, TokId::BIGINT, TokId::FLOAT, TokId::STRING, TokId::ARRAY];
+const KEYS: [TokId; 4] = [TokId::BOOLEAN, TokId::INT, TokId::BIGINT, TokId::STRING];
+const CALLABLE: [TokId; 2] = [TokId::QUOTE, TokId::FUNCREF];
+// everything but the types, what can be printed and pushed into an array
+const VALUES: [TokId; 10] = [
+    TokId::STRING,
+    TokId::INT,
+    TokId::BIGINT,
+    TokId::FLOAT,
+    TokId::BOOLEAN,
+    TokId::ARRAY,
+    TokId::MAP,
+    TokId::INSTANCE,
+    TokId::QUOTE,
+    TokId::FUNCREF,
+];
+
+// checks every function before anything runs, the errors are sorted by where they are
+pub fn check(function_map: &HashMap<String, Function>) -> Vec<NumenError> {
+    let globals = function_map.get(GLOBAL).map(|fun| assigned(&fun.stack)).unwrap_or_default();
+    let program = Program::new(function_map, globals);
+    let mut errors: Vec<NumenError> = function_map
+        .iter()
+        .flat_map(|(name, fun)| program.run(name, fun).0)
+        .collect();
+    errors.sort_by_key(|err| err.pos.as_ref().map(|pos| (pos.file.clone(), pos.line, pos.col)));
+    errors
+}
+
+// checks one function against the ones it can call, globals are the
+// variables of the global code that it can see
+pub fn check_function(
+    name: &str,
+    fun: &Function,
+    function_map: &HashMap<String, Function>,
+    globals: &HashSet<String>,
+) -> Vec<NumenError> {
+    Program::new(function_map, globals.clone()).run(name, fun).0
+}
+
+// the names a function assigns to or binds with let, a word with one of
+// these names can be a variable
+fn assigned(stack: &[Object]) -> HashSet<String> {
+    let mut names: HashSet<String> = HashSet::new();
+    names.insert("it".to_string());
+    let mut binding = false;
+    let mut iter = stack.iter().filter(|tok| tok.id != TokId::LINEBREAK).peekable();
+    while let Some(tok) = iter.next() {
+        match tok.id {
+            TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT => {
+                if let Some(var) = iter.peek() {
+                    names.insert(var.rep.to_string());
+                }
+            }
+            TokId::BLOCK => binding = true,
+            TokId::AS => binding = false,
+            TokId::UNKNOWN if binding => {
+                names.insert(tok.rep.to_string());
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+// words that are no builtin, variable or function of the program, a quotation
+// sees the variables of the function it is written in, wherever it is called
+pub fn undefined(function_map: &HashMap<String, Function>) -> Vec<NumenError> {
+    let types = struct_types(function_map);
+    let globals = function_map.get(GLOBAL).map(|fun| assigned(&fun.stack)).unwrap_or_default();
+    let locals: HashMap<&String, HashSet<String>> = function_map
+        .iter()
+        .map(|(name, fun)| {
+            let mut names = assigned(&fun.stack);
+            names.extend(
+                typed(&fun.arguments, &types)
+                    .into_iter()
+                    .filter(|param| param.id == TokId::UNKNOWN)
+                    .map(|param| param.rep.to_string()),
+            );
+            (name, names)
+        })
+        .collect();
+    let mut errors: Vec<NumenError> = Vec::new();
+    for (name, fun) in function_map {
+        let visible = |word: &String| {
+            locals[name].contains(word) || (name != GLOBAL && globals.contains(word)) || function_map.contains_key(word)
+        };
+        let mut binding = false;
+        let mut iter = fun.stack.iter().filter(|tok| tok.id != TokId::LINEBREAK);
+        while let Some(tok) = iter.next() {
+            match tok.id {
+                TokId::BLOCK => binding = true,
+                TokId::AS => binding = false,
+                // the name that is assigned to
+                TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT => {
+                    iter.next();
+                }
+                TokId::UNKNOWN if !binding => {
+                    let word = tok.rep.to_string();
+                    let constant = CONSTANTS.iter().any(|(constant, _)| *constant == word);
+                    if BUILTINS.contains(&word.as_str()) || constant || visible(&word) {
+                        continue;
+                    }
+                    let mut names: Vec<&str> = BUILTINS.to_vec();
+                    names.extend(CONSTANTS.iter().map(|(constant, _)| *constant));
+                    names.extend(function_map.keys().chain(&locals[name]).map(String::as_str));
+                    if name != GLOBAL {
+                        names.extend(globals.iter().map(String::as_str));
+                    }
+                    errors.push(undefined_word("CHECKER", &word, names.into_iter()).at(&tok.pos));
+                }
+                // a function pushed by name has to be one
+                TokId::FUNCREF => {
+                    let Value::STR(target) = &tok.rep else { continue };
+                    if BUILTINS.contains(&&**target) || function_map.contains_key(&**target) {
+                        continue;
+                    }
+                    let names = BUILTINS.iter().copied().chain(function_map.keys().map(String::as_str));
+                    errors.push(undefined_word("CHECKER", target, names).at(&tok.pos));
+                }
+                _ => {}
+            }
+        }
+    }
+    errors
+}
+
+// what a call leaves on the stack of its caller
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Effect {
+    UNKNOWN,
+    NOTHING,
+    // one value, of a kind if all of its rets agree on one
+    VALUE(Option<TokId>),
+}
+
+// what is inferred about a function without running it
+#[derive(Clone, PartialEq, Debug)]
+struct Summary {
+    effect: Effect,
+    // the kinds each argument can be, in the order they are taken
+    needs: Vec<Option<Vec<TokId>>>,
+}
+
+// the functions of a program with what is inferred about them
+struct Program<'a> {
+    function_map: &'a HashMap<String, Function>,
+    types: HashMap<String, Object>,
+    globals: HashSet<String>,
+    summaries: HashMap<String, Summary>,
+}
+
+impl<'a> Program<'a> {
+    fn new(function_map: &'a HashMap<String, Function>, globals: HashSet<String>) -> Program<'a> {
+        let mut program = Program {
+            function_map,
+            types: struct_types(function_map),
+            globals,
+            summaries: HashMap::new(),
+        };
+        // a pass only relies on what the passes before it inferred,
+        // so whatever it infers holds as well
+        for _ in 0..PASSES {
+            let summaries: HashMap<String, Summary> = function_map
+                .iter()
+                .map(|(name, fun)| (name.clone(), program.run(name, fun).1))
+                .collect();
+            if summaries == program.summaries {
+                break;
+            }
+            program.summaries = summaries;
+        }
+        program
+    }
+
+    // checks a function, returns its errors and what it infers about it
+    fn run(&self, name: &str, fun: &Function) -> (Vec<NumenError>, Summary) {
+        let mut checker = Checker {
+            program: self,
+            name,
+            fun,
+            // the global code has its variables to itself
+            globals: if name == GLOBAL { None } else { Some(&self.globals) },
+            assigned: assigned(&fun.stack),
+            iter: fun.stack.iter(),
+            rets: Vec::new(),
+            loops: Vec::new(),
+            depth: 0,
+            nested: 0,
+            needs: vec![None; fun.arguments.len()],
+            errors: Vec::new(),
+        };
+        // typed parameters start the stack, named ones are variables
+        let mut state = State::new(Vec::new());
+        for (i, param) in typed(&fun.arguments, &self.types).into_iter().enumerate() {
+            match param.id {
+                TokId::UNKNOWN => {
+                    state.heap.insert(param.rep.to_string(), Ty { kind: None, param: Some(i) });
+                    checker.assigned.insert(param.rep.to_string());
+                }
+                typ => state.stack.push(Ty { kind: instance_of(typ), param: None }),
+            }
+        }
+        checker.block(&mut state, &[]);
+        let summary = checker.summary(&state);
+        checker.signature(&state);
+        (checker.errors, summary)
+    }
+}
+
+// a value the checker knows about
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct Ty {
+    // None when it could be anything
+    kind: Option<TokId>,
+    // the parameter of the function the value comes straight from
+    param: Option<usize>,
+}
+
+const ANY: Ty = Ty { kind: None, param: None };
+
+fn of(kind: TokId) -> Ty {
+    Ty { kind: Some(kind), param: None }
+}
+
+// what is known about the stack and the variables at one point of a function
+#[derive(Clone)]
+struct State {
+    stack: Vec<Ty>,
+    // false when there can be values below the known ones
+    exact: bool,
+    // no path gets here, the code is after a ret, break or continue
+    dead: bool,
+    // variables that surely exist
+    heap: HashMap<String, Ty>,
+    // variables of the if branches open in the current block
+    scopes: Vec<HashMap<String, Ty>>,
+}
+
+impl State {
+    fn new(stack: Vec<Ty>) -> State {
+        State {
+            stack,
+            exact: true,
+            dead: false,
+            heap: HashMap::new(),
+            scopes: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, ty: Ty) {
+        if !self.dead {
+            self.stack.push(ty);
+        }
+    }
+
+    // None when the stack is surely empty
+    fn pop(&mut self) -> Option<Ty> {
+        match self.stack.pop() {
+            Some(ty) => Some(ty),
+            None if self.exact => None,
+            None => Some(ANY),
+        }
+    }
+
+    // after something with an unknown effect nothing is known about the stack
+    fn lose(&mut self) {
+        self.stack.clear();
+        self.exact = false;
+    }
+
+    fn kill(&mut self) {
+        self.lose();
+        self.dead = true;
+    }
+
+    // a block with a stack of its own, the if scopes around it can't be seen inside
+    fn frame(&self, stack: Vec<Ty>) -> State {
+        State {
+            stack,
+            exact: !self.dead,
+            dead: self.dead,
+            heap: self.heap.clone(),
+            scopes: Vec::new(),
+        }
+    }
+
+    // an array or map literal, it sees the variables around it
+    fn literal(&self) -> State {
+        State {
+            scopes: self.scopes.clone(),
+            ..self.frame(Vec::new())
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<Ty> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.heap.get(name))
+            .copied()
+    }
+
+    // the state after one of two paths, whatever differs between them is unknown
+    fn merge(self, other: State) -> State {
+        if self.dead {
+            return other;
+        }
+        if other.dead {
+            return self;
+        }
+        let mut merged = self;
+        if merged.stack.len() == other.stack.len() {
+            merged.stack = merged.stack.iter().zip(&other.stack).map(|(a, b)| join(*a, *b)).collect();
+            merged.exact &= other.exact;
+        } else {
+            merged.lose();
+        }
+        merged.heap = join_vars(&merged.heap, &other.heap);
+        merged.scopes = merged.scopes.iter().zip(&other.scopes).map(|(a, b)| join_vars(a, b)).collect();
+        merged
+    }
+
+    // a let block ran once, what it leaves comes back in reverse
+    fn after_block(&mut self, block: State) {
+        if block.dead {
+            self.kill();
+            return;
+        }
+        self.heap = block.heap;
+        if block.exact {
+            self.stack.extend(block.stack.into_iter().rev());
+        } else {
+            self.lose();
+        }
+    }
+
+    // a loop ran any number of times, so the values it leaves can't be counted
+    fn after_loop(&mut self, body: State) {
+        if !body.dead {
+            if !body.exact || !body.stack.is_empty() {
+                self.lose();
+            }
+            self.heap = join_vars(&self.heap, &body.heap);
+        }
+        self.heap.remove("it");
+    }
+
+    fn after_literal(&mut self, items: State, id: TokId) {
+        if items.dead {
+            self.kill();
+            return;
+        }
+        self.heap = items.heap;
+        self.scopes = items.scopes;
+        self.push(of(id));
+    }
+}
+
+fn join(a: Ty, b: Ty) -> Ty {
+    Ty {
+        kind: if a.kind == b.kind { a.kind } else { None },
+        param: if a.param == b.param { a.param } else { None },
+    }
+}
+
+// variables that exist on only one of the paths may not exist
+fn join_vars(a: &HashMap<String, Ty>, b: &HashMap<String, Ty>) -> HashMap<String, Ty> {
+    a.iter()
+        .filter_map(|(name, ty)| b.get(name).map(|other| (name.clone(), join(*ty, *other))))
+        .collect()
+}
+
+// the states at the breaks and continues of a loop
+struct Exits {
+    // blocks open at the body of the loop, a break from deeper merges more of them
+    depth: usize,
+    breaks: Vec<State>,
+    continues: Vec<State>,
+}
+
+impl Exits {
+    // the body can be left at its end and at every break and continue
+    fn merge(self, body: State) -> State {
+        self.breaks.into_iter().chain(self.continues).fold(body, State::merge)
+    }
+}
+
+struct Checker<'a> {
+    program: &'a Program<'a>,
+    // the function being checked, it can call itself before it is in the map
+    name: &'a str,
+    fun: &'a Function,
+    globals: Option<&'a HashSet<String>>,
+    assigned: HashSet<String>,
+    iter: Iter<'a, Object>,
+    // what each ret gives back and where
+    rets: Vec<(Ty, Pos)>,
+    loops: Vec<Exits>,
+    // blocks with their own stack open at this point
+    depth: usize,
+    // blocks of any kind open at this point, the code in them may not run
+    nested: usize,
+    // the kinds the parameters have to be for the function to get through
+    needs: Vec<Option<Vec<TokId>>>,
+    errors: Vec<NumenError>,
+}
+
+impl<'a> Checker<'a> {
+    fn error(&mut self, kind: ErrorKind, message: String, pos: &Pos) {
+        self.errors.push(NumenError::new(kind, message).at(pos));
+    }
+
+    // pops one value, an empty stack is reported once
+    fn pop(&mut self, state: &mut State, what: &str, pos: &Pos) -> Ty {
+        state.pop().unwrap_or_else(|| {
+            self.error(
+                ErrorKind::STACKUNDERFLOW,
+                format!("CHECKER: {} needs more values than the stack has", what),
+                pos,
+            );
+            state.lose();
+            ANY
+        })
+    }
+
+    // pops a value that has to be of one of the kinds
+    fn expect(&mut self, state: &mut State, allowed: &[TokId], what: &str, pos: &Pos) -> Ty {
+        let ty = self.pop(state, what, pos);
+        self.demand(ty, allowed, what, pos);
+        ty
+    }
+
+    // a value has to be of one of the kinds, so does the parameter it comes from
+    fn demand(&mut self, ty: Ty, allowed: &[TokId], what: &str, pos: &Pos) {
+        match ty.kind {
+            Some(kind) if !allowed.contains(&kind) => self.error(
+                ErrorKind::TYPEMISMATCH,
+                format!("CHECKER: {} needs {} but gets {}", what, describe(allowed), article(kind)),
+                pos,
+            ),
+            Some(_) => {}
+            None => self.need(ty, allowed),
+        }
+    }
+
+    // code that runs on every call tells what kinds the parameters have to be
+    fn need(&mut self, ty: Ty, allowed: &[TokId]) {
+        let Some(param) = ty.param else {
+            return;
+        };
+        if self.nested > 0 || !self.rets.is_empty() {
+            return;
+        }
+        match &mut self.needs[param] {
+            Some(kinds) => {
+                let narrower: Vec<TokId> = kinds.iter().copied().filter(|kind| allowed.contains(kind)).collect();
+                if !narrower.is_empty() {
+                    *kinds = narrower;
+                }
+            }
+            slot => *slot = Some(allowed.to_vec()),
+        }
+    }
+
+    // a value bound to a typed parameter of a function or let
+    fn typecheck(&mut self, param: &Object, ty: Ty, whose: &str, pos: &Pos) {
+        let Some(expected) = instance_of(param.id) else {
+            return;
+        };
+        match ty.kind {
+            Some(kind) if kind != expected => self.error(
+                ErrorKind::TYPEMISMATCH,
+                format!("CHECKER: {} gets {} for its {} parameter", whose, article(kind), param.rep),
+                pos,
+            ),
+            Some(_) => {}
+            None => self.need(ty, &[expected]),
+        }
+    }
+
+    // checks until one of the terminators on this level and returns it,
+    // None when the code ends first, the compiler reports missing ends
+    fn block(&mut self, state: &mut State, terminators: &[TokId]) -> Option<&'a Object> {
+        while let Some(tok) = self.iter.next() {
+            if terminators.contains(&tok.id) {
+                return Some(tok);
+            }
+            let pos = &tok.pos;
+            match tok.id {
+                TokId::IF => {
+                    self.expect(state, &[TokId::BOOLEAN], "if", pos);
+                    self.nested += 1;
+                    let mut then = state.clone();
+                    then.scopes.push(HashMap::new());
+                    let end = self.block(&mut then, &[TokId::ELSE, TokId::END]);
+                    then.scopes.pop();
+                    let mut other = state.clone();
+                    if end.is_some_and(|end| end.id == TokId::ELSE) {
+                        other.scopes.push(HashMap::new());
+                        self.block(&mut other, &[TokId::END]);
+                        other.scopes.pop();
+                    }
+                    self.nested -= 1;
+                    *state = then.merge(other);
+                }
+                TokId::LOOP => {
+                    self.expect(state, &[TokId::INT], "loop", pos);
+                    let mut body = state.frame(Vec::new());
+                    body.heap.insert("it".to_string(), of(TokId::INT));
+                    let exits = self.looped(&mut body, None);
+                    state.after_loop(exits.merge(body));
+                }
+                TokId::WHILE => {
+                    // a while true loop only ends at a break
+                    let forever = self.forever();
+                    let mut body = state.frame(Vec::new());
+                    let exits = self.looped(&mut body, Some(pos));
+                    let ends = !forever || !exits.breaks.is_empty();
+                    state.after_loop(exits.merge(body));
+                    if !ends {
+                        state.kill();
+                    }
+                }
+                TokId::BLOCK => self.let_block(state, pos),
+                TokId::ARRAYBEGIN | TokId::MAPBEGIN => {
+                    let (close, id) = match tok.id {
+                        TokId::ARRAYBEGIN => (TokId::ARRAYEND, TokId::ARRAY),
+                        _ => (TokId::MAPEND, TokId::MAP),
+                    };
+                    let mut items = state.literal();
+                    self.depth += 1;
+                    self.nested += 1;
+                    self.block(&mut items, &[close]);
+                    self.depth -= 1;
+                    self.nested -= 1;
+                    if id == TokId::MAP {
+                        self.map_items(&items, pos);
+                    }
+                    state.after_literal(items, id);
+                }
+                TokId::QUOTEBEGIN => {
+                    // a quotation runs on whatever stack it is called with
+                    let mut body = State::new(Vec::new());
+                    body.exact = false;
+                    body.dead = state.dead;
+                    let loops = std::mem::take(&mut self.loops);
+                    let rets = self.rets.len();
+                    self.nested += 1;
+                    self.block(&mut body, &[TokId::QUOTEEND]);
+                    self.nested -= 1;
+                    self.loops = loops;
+                    self.rets.truncate(rets);
+                    state.push(of(TokId::QUOTE));
+                }
+                TokId::BREAK | TokId::CONTINUE => {
+                    if let Some(exits) = self.loops.last_mut() {
+                        let mut exit = state.clone();
+                        if self.depth > exits.depth {
+                            exit.lose();
+                        }
+                        if tok.id == TokId::BREAK {
+                            exits.breaks.push(exit);
+                        } else {
+                            exits.continues.push(exit);
+                        }
+                    }
+                    state.kill();
+                }
+                TokId::RET => {
+                    if !state.dead {
+                        // the global code and main have no caller to return to
+                        let ty = match state.pop() {
+                            Some(ty) => ty,
+                            None if self.name == GLOBAL || self.name == MAIN => ANY,
+                            None => {
+                                self.error(
+                                    ErrorKind::STACKUNDERFLOW,
+                                    "CHECKER: ret has nothing on the stack to return".to_string(),
+                                    pos,
+                                );
+                                ANY
+                            }
+                        };
+                        self.rets.push((ty, pos.clone()));
+                    }
+                    state.kill();
+                }
+                TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT => {
+                    let Some(var) = self.iter.by_ref().find(|tok| tok.id != TokId::LINEBREAK) else {
+                        continue;
+                    };
+                    let name = var.rep.to_string();
+                    let ty = self.pop(state, &format!("{} {}", tok.rep, name), pos);
+                    if tok.id == TokId::RETURNINGASSIGNMENT {
+                        state.push(ty);
+                    }
+                    self.assign(state, name, ty);
+                }
+                TokId::UNKNOWN => self.word(state, &tok.rep.to_string(), pos),
+                // map is a type, or maps an array when a quotation is on top, but not before is
+                TokId::TMAP if self.iter.clone().find(|tok| tok.id != TokId::LINEBREAK).is_some_and(|tok| tok.id == TokId::IS) => {
+                    state.push(of(TokId::TMAP));
+                }
+                TokId::TMAP => self.word(state, "map", pos),
+                TokId::PLUS | TokId::MINUS | TokId::MULTIPLY | TokId::DIVIDE | TokId::MOD | TokId::POWER
+                | TokId::IS | TokId::EQUALS | TokId::BIGGER | TokId::SMALLER
+                | TokId::BIGGEREQUALS | TokId::SMALLEREQUALS | TokId::NOTEQUALS => {
+                    self.operator(state, tok, pos);
+                }
+                TokId::AND | TokId::OR | TokId::XOR | TokId::NOT => self.logic(state, tok, pos),
+                TokId::INT | TokId::BIGINT | TokId::FLOAT | TokId::BOOLEAN | TokId::STRING | TokId::FUNCREF
+                | TokId::ARRAY | TokId::TINT | TokId::TBIGINT | TokId::TFLOAT | TokId::TSTRING | TokId::TBOOL
+                | TokId::TARRAY | TokId::TQUOTE | TokId::TFUNCREF | TokId::TSTRUCT => state.push(of(tok.id)),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // checks the body of a loop, for a while loop the condition comes first
+    fn looped(&mut self, body: &mut State, condition: Option<&Pos>) -> Exits {
+        self.depth += 1;
+        self.nested += 1;
+        self.loops.push(Exits { depth: self.depth, breaks: vec![], continues: vec![] });
+        if let Some(pos) = condition {
+            self.block(body, &[TokId::DO]);
+            self.expect(body, &[TokId::BOOLEAN], "while", pos);
+            // the values left by the condition are dropped
+            *body = body.frame(Vec::new());
+        }
+        self.block(body, &[TokId::END]);
+        self.depth -= 1;
+        self.nested -= 1;
+        self.loops.pop().unwrap_or(Exits { depth: 0, breaks: vec![], continues: vec![] })
+    }
+
+    // while true do, the condition is a plain true
+    fn forever(&self) -> bool {
+        let mut ahead = self.iter.clone().filter(|tok| tok.id != TokId::LINEBREAK);
+        matches!(ahead.next(), Some(tok) if tok.rep == Value::BOOL(true))
+            && ahead.next().is_some_and(|tok| tok.id == TokId::DO)
+    }
+
+    fn let_block(&mut self, state: &mut State, pos: &Pos) {
+        let mut params: Vec<Object> = Vec::new();
+        for param in self.iter.by_ref() {
+            match param.id {
+                TokId::AS => break,
+                TokId::LINEBREAK => {}
+                _ => params.push(param.clone()),
+            }
+        }
+        let mut stack: Vec<Ty> = Vec::new();
+        let mut names: Vec<(String, Ty)> = Vec::new();
+        for param in typed(&params, &self.program.types).iter().rev() {
+            let ty = self.pop(state, "let", pos);
+            if param.id == TokId::UNKNOWN {
+                names.push((param.rep.to_string(), ty));
+            } else {
+                self.typecheck(param, ty, "let", pos);
+                stack.push(Ty { kind: instance_of(param.id), param: None });
+            }
+        }
+        stack.reverse();
+        let mut block = state.frame(stack);
+        block.heap.extend(names);
+        self.depth += 1;
+        self.nested += 1;
+        self.block(&mut block, &[TokId::END]);
+        self.depth -= 1;
+        self.nested -= 1;
+        state.after_block(block);
+    }
+
+    // the keys of a map literal are at the even places of its items
+    fn map_items(&mut self, items: &State, pos: &Pos) {
+        if items.dead || !items.exact {
+            return;
+        }
+        if !items.stack.len().is_multiple_of(2) {
+            self.error(
+                ErrorKind::UNBALANCED,
+                "CHECKER: the map literal has a key without a value".to_string(),
+                pos,
+            );
+        }
+        for key in items.stack.iter().step_by(2) {
+            match key.kind {
+                Some(kind) if !KEYS.contains(&kind) => self.error(
+                    ErrorKind::TYPEMISMATCH,
+                    format!("CHECKER: {} can't be a key of a map", article(kind)),
+                    pos,
+                ),
+                Some(_) => {}
+                None => self.need(*key, &KEYS),
+            }
+        }
+    }
+
+    // mirrors where the interpreter puts a variable
+    fn assign(&mut self, state: &mut State, name: String, ty: Ty) {
+        if state.dead || self.globals.is_some_and(|globals| globals.contains(&name)) {
+            return;
+        }
+        if self.name == GLOBAL || state.scopes.is_empty() {
+            state.heap.insert(name, ty);
+        } else if let Some(slot) = state.heap.get_mut(&name) {
+            *slot = ty;
+        } else if let Some(slot) = state.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(&name)) {
+            *slot = ty;
+        } else if let Some(scope) = state.scopes.last_mut() {
+            scope.insert(name, ty);
+        }
+    }
+
+    // builtin, variable or function call, in the order the interpreter looks them up
+    fn word(&mut self, state: &mut State, name: &str, pos: &Pos) {
+        if CORE.contains(&name) && self.builtin(state, name, pos) {
+            return;
+        }
+        if let Some(ty) = state.var(name) {
+            state.push(ty);
+        } else if self.globals.is_some_and(|globals| globals.contains(name)) {
+            state.push(ANY);
+        } else if self.assigned.contains(name) && self.unassigned(name, state) {
+            self.error(
+                ErrorKind::UNDEFINED,
+                format!("CHECKER: {} is used before it is assigned", name),
+                pos,
+            );
+            state.lose();
+        } else if self.assigned.contains(name) {
+            // a variable on some paths only
+            state.lose();
+        } else if let Some(callee) = self.callee(name) {
+            self.call(state, name, callee, pos);
+        } else if self.builtin(state, name, pos) {
+            // a later builtin
+        } else if CONSTANTS.iter().any(|(constant, _)| *constant == name) {
+            state.push(of(TokId::FLOAT));
+        } else {
+            state.lose();
+        }
+    }
+
+    // code that always runs and nothing before it assigns the name, not even
+    // in a loop, let or quotation that may have run, so it is only assigned later
+    fn unassigned(&self, name: &str, state: &State) -> bool {
+        let here = self.fun.stack.len() - self.iter.len();
+        self.nested == 0
+            && !state.dead
+            && self.callee(name).is_none()
+            && !BUILTINS.contains(&name)
+            && !assigned(&self.fun.stack[..here]).contains(name)
+    }
+
+    fn callee(&self, name: &str) -> Option<&'a Function> {
+        if name == self.name {
+            Some(self.fun)
+        } else {
+            self.program.function_map.get(name)
+        }
+    }
+
+    // a call takes its arguments and leaves what its signature says or what is inferred
+    fn call(&mut self, state: &mut State, name: &str, callee: &Function, pos: &Pos) {
+        let program = self.program;
+        let summary = program.summaries.get(name);
+        for (i, param) in typed(&callee.arguments, &program.types).iter().enumerate() {
+            let ty = self.pop(state, name, pos);
+            if param.id != TokId::UNKNOWN {
+                self.typecheck(param, ty, name, pos);
+                continue;
+            }
+            let Some(kinds) = summary.and_then(|summary| summary.needs.get(i)).and_then(Option::as_ref) else {
+                continue;
+            };
+            match ty.kind {
+                Some(kind) if !kinds.contains(&kind) => self.error(
+                    ErrorKind::TYPEMISMATCH,
+                    format!("CHECKER: {} gets {} for {}, which it uses as {}", name, article(kind), param.rep, describe(kinds)),
+                    pos,
+                ),
+                Some(_) => {}
+                None => self.need(ty, kinds),
+            }
+        }
+        let effect = match &callee.results {
+            Some(results) => match results.first() {
+                Some(result) => Effect::VALUE(self.result_kind(result)),
+                None => Effect::NOTHING,
+            },
+            None => summary.map_or(Effect::UNKNOWN, |summary| summary.effect),
+        };
+        match effect {
+            Effect::UNKNOWN => state.lose(),
+            Effect::NOTHING => {}
+            Effect::VALUE(kind) => state.push(Ty { kind, param: None }),
+        }
+    }
+
+    // a result is a type or a name for a value of any type
+    fn result_kind(&self, result: &Object) -> Option<TokId> {
+        match result.id {
+            TokId::UNKNOWN if self.program.types.contains_key(&result.rep.to_string()) => Some(TokId::INSTANCE),
+            id => instance_of(id),
+        }
+    }
+
+    // what a call of the function leaves, known when it always or never rets
+    fn summary(&self, end: &State) -> Summary {
+        let effect = match (end.dead, self.rets.is_empty()) {
+            _ if self.name == GLOBAL || self.name == MAIN => Effect::UNKNOWN,
+            (false, true) => Effect::NOTHING,
+            (true, false) => Effect::VALUE(
+                self.rets
+                    .iter()
+                    .map(|(ty, _)| ty.kind)
+                    .reduce(|a, b| if a == b { a } else { None })
+                    .flatten(),
+            ),
+            _ => Effect::UNKNOWN,
+        };
+        Summary { effect, needs: self.needs.clone() }
+    }
+
+    // the rets of a function with a signature have to give back what it says
+    fn signature(&mut self, end: &State) {
+        let Some(results) = &self.fun.results else {
+            return;
+        };
+        let rets = std::mem::take(&mut self.rets);
+        let Some(result) = results.first() else {
+            for (_, pos) in rets {
+                self.error(
+                    ErrorKind::TYPEMISMATCH,
+                    format!("CHECKER: {} returns a value but its signature has no results", self.name),
+                    &pos,
+                );
+            }
+            return;
+        };
+        let expected = self.result_kind(result);
+        for (ty, pos) in rets {
+            if let (Some(kind), Some(expected)) = (ty.kind, expected) {
+                if kind != expected {
+                    self.error(
+                        ErrorKind::TYPEMISMATCH,
+                        format!("CHECKER: {} returns {} but its signature says {}", self.name, article(kind), result.rep),
+                        &pos,
+                    );
+                }
+            }
+        }
+        if !end.dead {
+            self.error(
+                ErrorKind::UNBALANCED,
+                format!("CHECKER: {} can end without returning the {} of its signature", self.name, result.rep),
+                &result.pos,
+            );
+        }
+    }
+
+    fn operator(&mut self, state: &mut State, tok: &Object, pos: &Pos) {
+        let what = tok.rep.to_string();
+        let second = self.pop(state, &what, pos);
+        let first = self.pop(state, &what, pos);
+        let result = match tok.id {
+            TokId::EQUALS | TokId::NOTEQUALS | TokId::IS => of(TokId::BOOLEAN),
+            // strings join strings and arrays join arrays, the rest is arithmetic
+            TokId::PLUS => match (first.kind, second.kind) {
+                (Some(a), Some(b)) if a == b && matches!(a, TokId::STRING | TokId::ARRAY) => of(a),
+                (Some(a @ (TokId::STRING | TokId::ARRAY)), None) => {
+                    self.need(second, &[a]);
+                    of(a)
+                }
+                (None, Some(b @ (TokId::STRING | TokId::ARRAY))) => {
+                    self.need(first, &[b]);
+                    of(b)
+                }
+                (None, None) => {
+                    self.need(first, &ADDABLE);
+                    self.need(second, &ADDABLE);
+                    ANY
+                }
+                _ => self.arithmetic(first, second, "added", pos),
+            },
+            TokId::MINUS => self.arithmetic(first, second, "subtracted", pos),
+            TokId::MULTIPLY => self.arithmetic(first, second, "multiplied", pos),
+            TokId::DIVIDE => self.arithmetic(first, second, "divided", pos),
+            TokId::MOD => self.arithmetic(first, second, "used to mod", pos),
+            TokId::POWER => self.arithmetic(first, second, "raised", pos),
+            _ => {
+                self.arithmetic(first, second, "compared", pos);
+                of(TokId::BOOLEAN)
+            }
+        };
+        state.push(result);
+    }
+
+    // the logic words take booleans, and and or take a quotation for the right side too
+    fn logic(&mut self, state: &mut State, tok: &Object, pos: &Pos) {
+        let what = tok.rep.to_string();
+        let quoted = matches!(tok.id, TokId::AND | TokId::OR)
+            && matches!(state.stack.last(), Some(Ty { kind: Some(TokId::QUOTE | TokId::FUNCREF), .. }));
+        if quoted {
+            state.stack.pop();
+        } else if tok.id != TokId::NOT {
+            self.expect(state, &[TokId::BOOLEAN], &what, pos);
+        }
+        self.expect(state, &[TokId::BOOLEAN], &what, pos);
+        state.push(of(TokId::BOOLEAN));
+    }
+
+    // the kind of the result of arithmetic on two numbers, like the interpreter
+    // an int stays an int unless the other one is a bigint or a float
+    fn arithmetic(&mut self, first: Ty, second: Ty, verb: &str, pos: &Pos) -> Ty {
+        match (first.kind, second.kind) {
+            (Some(a), Some(b)) if numeric(a) && numeric(b) => {
+                if a == TokId::FLOAT || b == TokId::FLOAT {
+                    of(TokId::FLOAT)
+                } else if a == TokId::BIGINT || b == TokId::BIGINT {
+                    of(TokId::BIGINT)
+                } else {
+                    of(TokId::INT)
+                }
+            }
+            (Some(a), Some(b)) => {
+                self.error(
+                    ErrorKind::TYPEMISMATCH,
+                    format!("CHECKER: {} and {} can't be {}", type_name(a), type_name(b), verb),
+                    pos,
+                );
+                ANY
+            }
+            (Some(a), None) | (None, Some(a)) if !numeric(a) => {
+                self.error(
+                    ErrorKind::TYPEMISMATCH,
+                    format!("CHECKER: {} can't be {}", article(a), verb),
+                    pos,
+                );
+                ANY
+            }
+            (a, b) => {
+                self.need(first, &NUMBERS);
+                self.need(second, &NUMBERS);
+                // a float with any number is a float
+                if a == Some(TokId::FLOAT) || b == Some(TokId::FLOAT) {
+                    of(TokId::FLOAT)
+                } else {
+                    ANY
+                }
+            }
+        }
+    }
+
+    // the stack effects of the builtin words and the kinds they take,
+    // false if there is none with that name
+    fn builtin(&mut self, state: &mut State, name: &str, pos: &Pos) -> bool {
+        match name {
+            "print" => {
+                self.expect(state, &VALUES, name, pos);
+            }
+            "drop" => {
+                self.pop(state, name, pos);
+            }
+            "assert" => {
+                self.pop(state, name, pos);
+                self.pop(state, name, pos);
+            }
+            "swap" => {
+                let second = self.pop(state, name, pos);
+                let first = self.pop(state, name, pos);
+                state.push(second);
+                state.push(first);
+            }
+            "clear" => {
+                state.stack.clear();
+                state.exact = !state.dead;
+            }
+            "rot" => {
+                let third = self.pop(state, name, pos);
+                let second = self.pop(state, name, pos);
+                let first = self.pop(state, name, pos);
+                state.push(third);
+                state.push(first);
+                state.push(second);
+            }
+            "copy" => {
+                let top = self.pop(state, name, pos);
+                state.push(top);
+                state.push(top);
+            }
+            "carry" => {
+                let second = self.pop(state, name, pos);
+                let first = self.pop(state, name, pos);
+                state.push(first);
+                state.push(second);
+                state.push(first);
+            }
+            "big" => {
+                self.expect(state, &[TokId::INT, TokId::BIGINT], name, pos);
+                state.push(of(TokId::BIGINT));
+            }
+            "band" | "bor" | "bxor" | "shl" | "shr" => {
+                self.expect(state, &[TokId::INT], name, pos);
+                self.expect(state, &[TokId::INT], name, pos);
+                state.push(of(TokId::INT));
+            }
+            "bnot" => {
+                self.expect(state, &[TokId::INT], name, pos);
+                state.push(of(TokId::INT));
+            }
+            "quot" | "div" | "rem" | "mod" | "pow" => {
+                let second = self.pop(state, name, pos);
+                let first = self.pop(state, name, pos);
+                let verb = match name {
+                    "quot" | "div" => "divided",
+                    "pow" => "raised",
+                    _ => "used to mod",
+                };
+                let result = self.arithmetic(first, second, verb, pos);
+                state.push(result);
+            }
+            "hex" | "bin" | "oct" => {
+                self.expect(state, &[TokId::INT], name, pos);
+                state.push(of(TokId::STRING));
+            }
+            "abs" | "sign" | "floor" | "ceil" | "round" | "trunc" => {
+                let item = self.expect(state, &NUMBERS, name, pos);
+                state.push(Ty { kind: item.kind, param: None });
+            }
+            "min" | "max" => {
+                let second = self.pop(state, name, pos);
+                let first = self.pop(state, name, pos);
+                let result = self.arithmetic(first, second, "compared", pos);
+                state.push(result);
+            }
+            "clamp" => {
+                let hi = self.pop(state, name, pos);
+                let lo = self.pop(state, name, pos);
+                let item = self.pop(state, name, pos);
+                let result = self.arithmetic(item, lo, "compared", pos);
+                let result = self.arithmetic(result, hi, "compared", pos);
+                state.push(result);
+            }
+            "sqrt" | "sin" | "cos" | "tan" | "exp" | "ln" | "log10" => {
+                self.expect(state, &NUMBERS, name, pos);
+                state.push(of(TokId::FLOAT));
+            }
+            "atan2" => {
+                self.expect(state, &NUMBERS, name, pos);
+                self.expect(state, &NUMBERS, name, pos);
+                state.push(of(TokId::FLOAT));
+            }
+            // the array can be on either side of what goes into it or of the index
+            "push" | "fetch" => {
+                let (item, verb) = match name {
+                    "push" => (&VALUES[..], "push"),
+                    _ => (&[TokId::INT][..], "fetch"),
+                };
+                let second = self.pop(state, name, pos);
+                let first = self.pop(state, name, pos);
+                match (first.kind, second.kind) {
+                    (Some(TokId::ARRAY), _) => self.demand(second, item, verb, pos),
+                    (_, Some(TokId::ARRAY)) => self.demand(first, item, verb, pos),
+                    (Some(a), Some(b)) => self.error(
+                        ErrorKind::TYPEMISMATCH,
+                        format!("CHECKER: {} needs an array but gets {} and {}", verb, article(a), article(b)),
+                        pos,
+                    ),
+                    (Some(_), None) => {
+                        self.demand(first, item, verb, pos);
+                        self.need(second, &[TokId::ARRAY]);
+                    }
+                    (None, Some(_)) => {
+                        self.demand(second, item, verb, pos);
+                        self.need(first, &[TokId::ARRAY]);
+                    }
+                    (None, None) => {}
+                }
+                state.push(if name == "push" { of(TokId::ARRAY) } else { ANY });
+            }
+            "len" => {
+                let item = self.pop(state, name, pos);
+                state.push(item);
+                state.push(of(TokId::INT));
+            }
+            "get" => {
+                self.pop(state, name, pos);
+                self.expect(state, &[TokId::MAP, TokId::INSTANCE], name, pos);
+                state.push(ANY);
+            }
+            "set" => {
+                self.pop(state, name, pos);
+                self.pop(state, name, pos);
+                let map = self.expect(state, &[TokId::MAP, TokId::INSTANCE], name, pos);
+                state.push(Ty { kind: map.kind, param: None });
+            }
+            "has" | "remove" => {
+                self.pop(state, name, pos);
+                self.expect(state, &[TokId::MAP], name, pos);
+                state.push(of(if name == "has" { TokId::BOOLEAN } else { TokId::MAP }));
+            }
+            "keys" | "values" => {
+                self.expect(state, &[TokId::MAP], name, pos);
+                state.push(of(TokId::ARRAY));
+            }
+            "map" => match state.stack.last().map(|top| top.kind) {
+                Some(Some(TokId::QUOTE | TokId::FUNCREF)) => {
+                    state.stack.pop();
+                    self.expect(state, &[TokId::ARRAY], name, pos);
+                    state.push(of(TokId::ARRAY));
+                }
+                Some(Some(_)) => state.push(of(TokId::TMAP)),
+                None if state.exact => state.push(of(TokId::TMAP)),
+                _ => state.lose(),
+            },
+            "filter" => {
+                self.expect(state, &CALLABLE, name, pos);
+                self.expect(state, &[TokId::ARRAY], name, pos);
+                state.push(of(TokId::ARRAY));
+            }
+            "fold" => {
+                self.expect(state, &CALLABLE, name, pos);
+                self.pop(state, name, pos);
+                self.expect(state, &[TokId::ARRAY], name, pos);
+                state.push(ANY);
+            }
+            "each" => {
+                self.expect(state, &CALLABLE, name, pos);
+                self.expect(state, &[TokId::ARRAY], name, pos);
+                state.lose();
+            }
+            // the stack effect depends on what is called or on the values
+            "call" | "apply" => {
+                self.expect(state, &CALLABLE, name, pos);
+                state.lose();
+            }
+            "exec" => {
+                self.expect(state, &[TokId::STRING], name, pos);
+                state.lose();
+            }
+            "new" => {
+                self.expect(state, &[TokId::TSTRUCT], name, pos);
+                state.lose();
+            }
+            "pop" => {
+                self.pop(state, name, pos);
+                state.lose();
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+fn numeric(id: TokId) -> bool {
+    NUMBERS.contains(&id)
+}
+
+// the kind of the values of a type
+fn instance_of(typ: TokId) -> Option<TokId> {
+    match typ {
+        TokId::TINT => Some(TokId::INT),
+        TokId::TBIGINT => Some(TokId::BIGINT),
+        TokId::TFLOAT => Some(TokId::FLOAT),
+        TokId::TSTRING => Some(TokId::STRING),
+        TokId::TBOOL => Some(TokId::BOOLEAN),
+        TokId::TARRAY => Some(TokId::ARRAY),
+        TokId::TMAP => Some(TokId::MAP),
+        TokId::TQUOTE => Some(TokId::QUOTE),
+        TokId::TFUNCREF => Some(TokId::FUNCREF),
+        TokId::TSTRUCT => Some(TokId::INSTANCE),
+        _ => None,
+    }
+}
+
+fn type_name(id: TokId) -> &'static str {
+    match id {
+        TokId::INT => "int",
+        TokId::BIGINT => "bigint",
+        TokId::FLOAT => "float",
+        TokId::STRING => "str",
+        TokId::BOOLEAN => "bool",
+        TokId::ARRAY => "array",
+        TokId::MAP => "map",
+        TokId::INSTANCE => "struct",
+        TokId::QUOTE => "quotation",
+        TokId::FUNCREF => "function",
+        _ => "type",
+    }
+}
+
+fn article(id: TokId) -> String {
+    let name = type_name(id);
+    if name.starts_with(['a', 'e', 'i', 'o', 'u']) {
+        format!("an {}", name)
+    } else {
+        format!("a {}", name)
+    }
+}
+
+// the kinds a value can be, for the errors
+fn describe(kinds: &[TokId]) -> String {
+    if kinds == NUMBERS {
+        "a number".to_string()
+    } else if kinds == ADDABLE {
+        "a number, a str or an array".to_string()
+    } else if kinds == VALUES {
+        "a value".to_string()
+    } else {
+        kinds.iter().map(|kind| article(*kind)).collect::<Vec<String>>().join(" or ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer_file;
+    use crate::parser::parse_file;
+
+    // the messages of everything the checker finds in the source
+    fn errors(source: &str) -> Vec<String> {
+        let fmap = parse_file(lexer_file(source, "test.nm").unwrap()).unwrap();
+        undefined(&fmap).into_iter().chain(check(&fmap)).map(|err| err.message).collect()
+    }
+
+    fn passes(source: &str) {
+        assert_eq!(errors(source), Vec::<String>::new(), "{}", source);
+    }
+
+    fn fails(source: &str, message: &str) {
+        let errors = errors(source);
+        assert!(errors.iter().any(|err| err.contains(message)), "{} gives {:?}", source, errors);
+    }
+
+    #[test]
+    fn reports_a_variable_used_before_it_is_assigned() {
+        fails("fun f as x print 5 = x end f", "x is used before it is assigned");
+        fails("x print 5 = x", "x is used before it is assigned");
+    }
+
+    #[test]
+    fn accepts_a_variable_that_may_be_assigned_before() {
+        passes("fun main as 3 loop it = last end last print end");
+        passes("0 = i while i 3 < do i 1 + = i i = last end last print");
+        passes("( 7 = x ) call x print");
+        passes("fun f n as n 0 > if 1 = x end x print end 1 f");
+        passes("fun f as 1 let y as y = x end x print end f");
+    }
+
+    #[test]
+    fn reports_what_is_sure_to_fail() {
+        fails("\"a\" 1 + print", "str and int can't be added");
+        fails("fun f int -- int as 1 + ret end \"x\" f print", "f gets a str for its int parameter");
+        fails("fun f -- str as 1 ret end f print", "f returns an int but its signature says str");
+        fails("fun f -- int as 1 print end f", "f can end without returning the int of its signature");
+        fails("fun f as 1 + print end f", "+ needs more values than the stack has");
+        fails("fun f a b as 1 let x y as x y + print end end 1 2 f", "let needs more values than the stack has");
+        fails("[ 1 2 ] \"0\" fetch print", "fetch needs an int but gets a str");
+        fails("prnt", "prnt is no builtin, variable or function, did you mean print?");
+        // a quotation doesn't see the variables of the function that calls it
+        fails("fun g as 1 = k end fun f as ( k ) call end", "k is no builtin, variable or function");
+        fails("fun square x as x x * ret end 3 &sqare call print", "sqare is no builtin, variable or function, did you mean square?");
+    }
+
+    #[test]
+    fn accepts_what_may_work() {
+        passes("fun f n as 0 = total n loop total it + = total end total ret end 3 f print");
+        passes("fun f as [ 1 2 ] ( copy = last ) each last print drop drop end f");
+        passes("fun f a b as a b let x y as x y + print end end 1 2 f");
+        passes("fun f as 1 2 let int y as y + print end end f");
+        passes("fun f n as while true do n 0 > if n ret end n 1 + = n end end 1 f print");
+        passes("fun f x as x 0 > if x ret else 0 x - ret end end -3 f 1 + print");
+        passes("fun f q as 1 q call ret end ( 1 + ) f print");
+        passes("fun f as 2 = k ( k * ) ret end 3 f call print");
+        passes("fun square x as x x * ret end 3 &square call print [ 1 ] &len call print");
+    }
+
+    // what the checker infers about the function f of the source
+    fn summary(source: &str) -> Summary {
+        let fmap = parse_file(lexer_file(source, "test.nm").unwrap()).unwrap();
+        Program::new(&fmap, HashSet::new()).summaries["f"].clone()
+    }
+
+    #[test]
+    fn infers_what_parameters_need() {
+        assert_eq!(summary("fun f x as x 1 + ret end").needs, vec![Some(NUMBERS.to_vec())]);
+        assert_eq!(summary("fun f x as x \"a\" + ret end").needs, vec![Some(vec![TokId::STRING])]);
+        assert_eq!(summary("fun f x as x 0 fetch ret end").needs, vec![Some(vec![TokId::ARRAY])]);
+        // the first parameter is the top of the stack
+        assert_eq!(summary("fun f s n as n 1 + s \"a\" + end").needs, vec![Some(vec![TokId::STRING]), Some(NUMBERS.to_vec())]);
+        // only what is done before any branch counts
+        assert_eq!(summary("fun f x as true if x 1 + print end end").needs, vec![None]);
+        assert_eq!(summary("fun f x as x print end").needs, vec![Some(VALUES.to_vec())]);
+    }
+
+    #[test]
+    fn infers_what_functions_leave() {
+        assert_eq!(summary("fun f as 1 ret end").effect, Effect::VALUE(Some(TokId::INT)));
+        assert_eq!(summary("fun f x as x 0 > if 1 ret else 2.0 ret end end").effect, Effect::VALUE(None));
+        assert_eq!(summary("fun f as 1 print end").effect, Effect::NOTHING);
+        // one path ends with ret and the other doesn't
+        assert_eq!(summary("fun f x as x 0 > if 1 ret end end").effect, Effect::UNKNOWN);
+        assert_eq!(summary("fun f x as x loop it 2 > if it ret end end end").effect, Effect::UNKNOWN);
+        // a call to it leaves what it leaves
+        assert_eq!(summary("fun g as \"a\" ret end fun f as g ret end").effect, Effect::VALUE(Some(TokId::STRING)));
+    }
+}
+
@@ -0,0 +1,466 @@
+use crate::error::{ErrorKind, NumenError};
+use crate::head::{Function, Object, Pos, TokId, Value};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub enum Op {
+    // pushes a literal or a type name
+    PUSH(Object),
+    // arithmetic, comparisons and is
+    OPERATOR(TokId),
+    // builtin, variable or function call, looked up when it runs
+    WORD(Rc<str>),
+    // = and =>, true keeps the value on the stack
+    ASSIGN(Rc<str>, bool),
+    // pops the condition, jumps to the else branch or past the end when false
+    IF(usize),
+    // end of the taken branch, closes its scope and jumps past the else branch
+    ELSE(usize),
+    // variable scope of an if or else branch
+    SCOPE,
+    ENDSCOPE,
+    // runs the following code on a fresh stack, true merges it back on leave
+    ENTER(bool),
+    LEAVE,
+    // binds the parameters of a let and enters its block
+    LET(Vec<Object>),
+    // pops the times and starts counting, jumps to LOOPDONE when there is nothing to do
+    LOOP(usize),
+    // sets it and enters the body
+    LOOPBODY,
+    // counts up and jumps back to LOOPBODY until done
+    LOOPNEXT(usize),
+    LOOPDONE,
+    // pops the condition and leaves its block, jumps past the loop when false
+    WHILE(usize),
+    JUMP(usize),
+    RET,
+    // break and continue, leaves that many blocks and jumps
+    UNWIND(usize, usize),
+    // the elements of an array or map literal run on a fresh stack,
+    // the end turns it into the array or into the map of its pairs
+    COLLECT,
+    ARRAYEND,
+    MAPEND,
+}
+
+#[derive(Debug, Clone)]
+pub struct Instr {
+    pub op: Op,
+    pub pos: Pos,
+}
+
+// a quotation literal like ( 2 * ), the source is kept to print it
+#[derive(Debug)]
+pub struct Quotation {
+    pub source: Vec<Object>,
+    pub instrs: Vec<Instr>,
+}
+
+// quotations are equal when they are written the same
+impl PartialEq for Quotation {
+    fn eq(&self, other: &Quotation) -> bool {
+        self.source == other.source
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub arguments: Vec<Object>,
+    pub instrs: Vec<Instr>,
+}
+
+// lowers every function into bytecode
+pub fn compile(function_map: &HashMap<String, Function>) -> Result<HashMap<String, Code>, NumenError> {
+    let types = struct_types(function_map);
+    let mut program: HashMap<String, Code> = HashMap::new();
+    for (name, fun) in function_map {
+        program.insert(name.clone(), compile_function(fun, &types)?);
+    }
+    Ok(program)
+}
+
+// the struct types of the program by name, their words push them
+pub fn struct_types(function_map: &HashMap<String, Function>) -> HashMap<String, Object> {
+    function_map
+        .values()
+        .flat_map(|fun| fun.stack.iter())
+        .filter(|obj| obj.id == TokId::TSTRUCT)
+        .map(|obj| (obj.rep.to_string(), obj.clone()))
+        .collect()
+}
+
+pub fn compile_function(fun: &Function, types: &HashMap<String, Object>) -> Result<Code, NumenError> {
+    Ok(Code {
+        arguments: typed(&fun.arguments, types),
+        instrs: compile_stack(&fun.stack, types)?,
+    })
+}
+
+pub fn compile_stack(stack: &[Object], types: &HashMap<String, Object>) -> Result<Vec<Instr>, NumenError> {
+    let mut compiler = Compiler {
+        iter: stack.iter(),
+        instrs: Vec::new(),
+        depth: 0,
+        loops: Vec::new(),
+        types,
+        quotation: false,
+    };
+    compiler.block(&[])?;
+    Ok(compiler.instrs)
+}
+
+// jumps of break and continue, patched once the loop is compiled
+struct LoopJumps {
+    // blocks that were open before the loop
+    depth: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    iter: std::slice::Iter<'a, Object>,
+    instrs: Vec<Instr>,
+    // blocks with their own stack open at this point
+    depth: usize,
+    loops: Vec<LoopJumps>,
+    types: &'a HashMap<String, Object>,
+    // quotations can't ret, there is no function to return from
+    quotation: bool,
+}
+
+// parameters named after a struct take values of that struct, like int does
+pub fn typed(params: &[Object], types: &HashMap<String, Object>) -> Vec<Object> {
+    params
+        .iter()
+        .map(|param| match (param.id, types.get(&param.rep.to_string())) {
+            (TokId::UNKNOWN, Some(typ)) => Object { pos: param.pos.clone(), ..typ.clone() },
+            _ => param.clone(),
+        })
+        .collect()
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, op: Op, pos: &Pos) -> usize {
+        self.instrs.push(Instr { op, pos: pos.clone() });
+        self.instrs.len() - 1
+    }
+
+    fn patch(&mut self, at: usize, target: usize) {
+        match &mut self.instrs[at].op {
+            Op::IF(to) | Op::ELSE(to) | Op::LOOP(to) | Op::WHILE(to) | Op::JUMP(to) | Op::UNWIND(_, to) => *to = target,
+            _ => {}
+        }
+    }
+
+    // compiles until one of the terminators on this level and returns it,
+    // None when the code ends first
+    fn block(&mut self, terminators: &[TokId]) -> Result<Option<&'a Object>, NumenError> {
+        while let Some(tok) = self.iter.next() {
+            if terminators.contains(&tok.id) {
+                return Ok(Some(tok));
+            }
+            match tok.id {
+                TokId::LINEBREAK | TokId::IMPORT => {}
+                TokId::IF => {
+                    let cond = self.emit(Op::IF(0), &tok.pos);
+                    let end = self.until(&[TokId::ELSE, TokId::END], "if", &tok.pos)?;
+                    if end.id == TokId::ELSE {
+                        let skip = self.emit(Op::ELSE(0), &end.pos);
+                        let else_start = self.emit(Op::SCOPE, &end.pos);
+                        self.patch(cond, else_start);
+                        let end = self.until(&[TokId::END], "if ... else", &end.pos)?;
+                        self.emit(Op::ENDSCOPE, &end.pos);
+                        self.patch(skip, self.instrs.len());
+                    } else {
+                        self.emit(Op::ENDSCOPE, &end.pos);
+                        self.patch(cond, self.instrs.len());
+                    }
+                }
+                TokId::LOOP => {
+                    let start = self.emit(Op::LOOP(0), &tok.pos);
+                    self.loops.push(LoopJumps { depth: self.depth, breaks: vec![], continues: vec![] });
+                    let body = self.emit(Op::LOOPBODY, &tok.pos);
+                    self.depth += 1;
+                    self.until(&[TokId::END], "loop", &tok.pos)?;
+                    self.emit(Op::LEAVE, &tok.pos);
+                    self.depth -= 1;
+                    let next = self.emit(Op::LOOPNEXT(body), &tok.pos);
+                    let done = self.emit(Op::LOOPDONE, &tok.pos);
+                    self.patch(start, done);
+                    self.close_loop(next, done);
+                }
+                TokId::WHILE => {
+                    self.loops.push(LoopJumps { depth: self.depth, breaks: vec![], continues: vec![] });
+                    let start = self.emit(Op::ENTER(false), &tok.pos);
+                    self.depth += 1;
+                    // an end before the do closes the while, not a block of the condition
+                    let cond_end = self.until(&[TokId::DO, TokId::END], "while", &tok.pos)?;
+                    if cond_end.id == TokId::END {
+                        return Err(NumenError::new(
+                            ErrorKind::UNBALANCED,
+                            "COMPILER: 'do' is missing for the while statement",
+                        ).at(&tok.pos));
+                    }
+                    self.depth -= 1;
+                    let cond = self.emit(Op::WHILE(0), &cond_end.pos);
+                    self.emit(Op::ENTER(true), &tok.pos);
+                    self.depth += 1;
+                    self.until(&[TokId::END], "while ... do", &tok.pos)?;
+                    self.emit(Op::LEAVE, &tok.pos);
+                    self.depth -= 1;
+                    self.emit(Op::JUMP(start), &tok.pos);
+                    let exit = self.instrs.len();
+                    self.patch(cond, exit);
+                    self.close_loop(start, exit);
+                }
+                TokId::BLOCK => {
+                    let mut params: Vec<Object> = Vec::new();
+                    loop {
+                        let param = self.iter.next().ok_or_else(|| NumenError::new(
+                            ErrorKind::UNBALANCED,
+                            "COMPILER: 'as' is missing for the let statement",
+                        ).at(&tok.pos))?;
+                        match param.id {
+                            TokId::AS => break,
+                            TokId::LINEBREAK => {}
+                            _ => params.push(param.clone()),
+                        }
+                    }
+                    self.emit(Op::LET(typed(&params, self.types)), &tok.pos);
+                    self.depth += 1;
+                    self.until(&[TokId::END], "let ... as", &tok.pos)?;
+                    self.emit(Op::LEAVE, &tok.pos);
+                    self.depth -= 1;
+                }
+                TokId::ARRAYBEGIN => {
+                    let start = self.emit(Op::COLLECT, &tok.pos);
+                    self.depth += 1;
+                    self.until(&[TokId::ARRAYEND], "array", &tok.pos)?;
+                    self.depth -= 1;
+                    self.emit(Op::ARRAYEND, &tok.pos);
+                    self.fold_array(start);
+                }
+                TokId::QUOTEBEGIN => {
+                    let quote = self.quotation_literal(&tok.pos)?;
+                    self.emit(Op::PUSH(Object {
+                        id: TokId::QUOTE,
+                        rep: Value::QUOTE(Rc::new(quote), None),
+                        pos: tok.pos.clone(),
+                    }), &tok.pos);
+                }
+                TokId::TMAP => {
+                    // map is the type of maps, but with a quotation on top it maps an array,
+                    // right before is it is always the type
+                    if self.iter.clone().find(|t| t.id != TokId::LINEBREAK).is_some_and(|t| t.id == TokId::IS) {
+                        self.emit(Op::PUSH(tok.clone()), &tok.pos);
+                    } else {
+                        self.emit(Op::WORD("map".into()), &tok.pos);
+                    }
+                }
+                TokId::MAPBEGIN => {
+                    self.emit(Op::COLLECT, &tok.pos);
+                    self.depth += 1;
+                    self.until(&[TokId::MAPEND], "map", &tok.pos)?;
+                    self.depth -= 1;
+                    self.emit(Op::MAPEND, &tok.pos);
+                }
+                TokId::BREAK | TokId::CONTINUE => {
+                    let depth = self.depth;
+                    let at = self.instrs.len();
+                    let Some(jumps) = self.loops.last_mut() else {
+                        return Err(NumenError::new(
+                            ErrorKind::SYNTAX,
+                            format!("COMPILER: {} is only allowed inside a loop", tok.rep),
+                        ).at(&tok.pos));
+                    };
+                    let blocks = depth - jumps.depth;
+                    if tok.id == TokId::BREAK {
+                        jumps.breaks.push(at);
+                    } else {
+                        jumps.continues.push(at);
+                    }
+                    self.emit(Op::UNWIND(blocks, 0), &tok.pos);
+                }
+                TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT => {
+                    let var = self.iter.by_ref().find(|t| t.id != TokId::LINEBREAK).ok_or_else(|| NumenError::new(
+                        ErrorKind::SYNTAX,
+                        "COMPILER: no variable name to assign in to",
+                    ).at(&tok.pos))?;
+                    let (TokId::UNKNOWN, Value::STR(name)) = (var.id, &var.rep) else {
+                        return Err(NumenError::new(
+                            ErrorKind::SYNTAX,
+                            format!("COMPILER: {} -> {} is not a variable name", var.id, var.rep),
+                        ).at(&var.pos));
+                    };
+                    self.emit(Op::ASSIGN(name.clone(), tok.id == TokId::RETURNINGASSIGNMENT), &tok.pos);
+                }
+                TokId::RET => {
+                    if self.quotation {
+                        return Err(NumenError::new(
+                            ErrorKind::SYNTAX,
+                            "COMPILER: ret can't be used inside a quotation",
+                        ).at(&tok.pos));
+                    }
+                    self.emit(Op::RET, &tok.pos);
+                }
+                TokId::UNKNOWN => {
+                    let Value::STR(name) = &tok.rep else {
+                        return Err(NumenError::new(ErrorKind::SYNTAX, "COMPILER: word is not a name").at(&tok.pos));
+                    };
+                    self.emit(Op::WORD(name.clone()), &tok.pos);
+                }
+                TokId::PLUS | TokId::MINUS | TokId::MULTIPLY | TokId::DIVIDE | TokId::MOD | TokId::POWER
+                | TokId::IS | TokId::EQUALS | TokId::BIGGER | TokId::SMALLER
+                | TokId::BIGGEREQUALS | TokId::SMALLEREQUALS | TokId::NOTEQUALS
+                | TokId::AND | TokId::OR | TokId::XOR | TokId::NOT => {
+                    self.emit(Op::OPERATOR(tok.id), &tok.pos);
+                }
+                TokId::FUNCTION => {
+                    return Err(NumenError::new(
+                        ErrorKind::SYNTAX,
+                        "COMPILER: can't declare functions inside a functions",
+                    ).at(&tok.pos));
+                }
+                TokId::END | TokId::ELSE | TokId::DO | TokId::AS | TokId::EFFECT | TokId::ARRAYEND | TokId::MAPEND | TokId::QUOTEEND => {
+                    return Err(NumenError::new(
+                        ErrorKind::UNBALANCED,
+                        format!("COMPILER: unexpected {}", tok.rep),
+                    ).at(&tok.pos));
+                }
+                _ => {
+                    self.emit(Op::PUSH(tok.clone()), &tok.pos);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    // the body of a statement, it has to be closed by one of the terminators
+    fn until(&mut self, terminators: &[TokId], what: &str, pos: &Pos) -> Result<&'a Object, NumenError> {
+        self.block(terminators)?.ok_or_else(|| {
+            let expected = if terminators.contains(&TokId::DO) {
+                "do"
+            } else if terminators.contains(&TokId::ARRAYEND) {
+                "]"
+            } else if terminators.contains(&TokId::MAPEND) {
+                "}"
+            } else {
+                "end"
+            };
+            NumenError::new(
+                ErrorKind::UNBALANCED,
+                format!("COMPILER: '{}' is missing for the {} statement", expected, what),
+            ).at(pos)
+        })
+    }
+
+    // the code up to the matching ), compiled on its own
+    fn quotation_literal(&mut self, pos: &Pos) -> Result<Quotation, NumenError> {
+        let mut source: Vec<Object> = Vec::new();
+        let mut nested = 0;
+        loop {
+            let tok = self.iter.next().ok_or_else(|| NumenError::new(
+                ErrorKind::UNBALANCED,
+                "COMPILER: ')' is missing for the quotation",
+            ).at(pos))?;
+            match tok.id {
+                TokId::QUOTEBEGIN => nested += 1,
+                TokId::QUOTEEND if nested == 0 => break,
+                TokId::QUOTEEND => nested -= 1,
+                _ => {}
+            }
+            source.push(tok.clone());
+        }
+        let mut inner = Compiler {
+            iter: source.iter(),
+            instrs: Vec::new(),
+            depth: 0,
+            loops: Vec::new(),
+            types: self.types,
+            quotation: true,
+        };
+        inner.block(&[])?;
+        let instrs = inner.instrs;
+        Ok(Quotation { source, instrs })
+    }
+
+    // an array of nothing but literals is pushed as a whole
+    fn fold_array(&mut self, start: usize) {
+        let mut items: Vec<Object> = Vec::new();
+        for instr in &self.instrs[start + 1..self.instrs.len() - 1] {
+            let Op::PUSH(obj) = &instr.op else {
+                return;
+            };
+            // a quotation takes the variables along when it is pushed
+            if obj.id == TokId::QUOTE {
+                return;
+            }
+            items.push(obj.clone());
+        }
+        let pos = self.instrs[start].pos.clone();
+        self.instrs.truncate(start);
+        self.emit(Op::PUSH(Object { id: TokId::ARRAY, rep: Value::ARR(items), pos: pos.clone() }), &pos);
+    }
+
+    fn close_loop(&mut self, next: usize, exit: usize) {
+        let Some(jumps) = self.loops.pop() else {
+            return;
+        };
+        for at in jumps.breaks {
+            self.patch(at, exit);
+        }
+        for at in jumps.continues {
+            self.patch(at, next);
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::head::GLOBAL;
+    use crate::lexer::lexer_file;
+    use crate::parser::parse_file;
+
+    fn code(source: &str) -> Result<HashMap<String, Code>, NumenError> {
+        compile(&parse_file(lexer_file(source, "test.nm")?)?)
+    }
+
+    // the blocks every break and continue leaves and the op it jumps to
+    fn unwinds(source: &str) -> Vec<(usize, String)> {
+        let instrs = &code(source).unwrap()[GLOBAL].instrs;
+        let name = |at: usize| instrs.get(at).map_or("the end".into(), |instr| format!("{:?}", instr.op));
+        instrs
+            .iter()
+            .filter_map(|instr| match instr.op {
+                Op::UNWIND(blocks, to) => Some((blocks, name(to))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jumps_out_of_the_blocks_inside_a_loop() {
+        // the array literal and the body of the loop are left, the if has no stack of its own
+        assert_eq!(
+            unwinds("5 loop true if [ 1 break ] end continue end"),
+            vec![(2, "LOOPDONE".into()), (1, "LOOPNEXT(2)".into())],
+        );
+        // continue goes back to the condition, break past the loop
+        assert_eq!(
+            unwinds("while true do true if break end continue end"),
+            vec![(1, "the end".into()), (1, "ENTER(false)".into())],
+        );
+    }
+
+    #[test]
+    fn rejects_jumps_without_a_loop_or_function() {
+        let err = code("true if break end").unwrap_err();
+        assert!(err.message.contains("break is only allowed inside a loop"), "{}", err.message);
+        let err = code("fun f as 3 loop ( continue ) call end end").unwrap_err();
+        assert!(err.message.contains("continue is only allowed inside a loop"), "{}", err.message);
+        let err = code("fun f as ( 1 ret ) call end").unwrap_err();
+        assert!(err.message.contains("ret can't be used inside a quotation"), "{}", err.message);
+    }
+}
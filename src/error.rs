@@ -0,0 +1,117 @@
+use crate::head::{Pos, GLOBAL};
+
+#[derive(PartialEq, Clone, Debug, Copy)]
+pub enum ErrorKind {
+    // reading files and resolving imports
+    IO,
+    IMPORT,
+    // malformed source, reserved or invalid names
+    SYNTAX,
+    // missing or extra end, else, do or as
+    UNBALANCED,
+    // popping from an empty stack
+    STACKUNDERFLOW,
+    // a value of the wrong type for an operation or parameter
+    TYPEMISMATCH,
+    // a literal that can't be read as its type
+    INVALIDVALUE,
+    // a function, variable or type that does not exist
+    UNDEFINED,
+    OUTOFBOUNDS,
+    // int overflow and division by zero
+    ARITHMETIC,
+    // a failed assert
+    ASSERTION,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumenError {
+    pub kind: ErrorKind,
+    pub message: String,
+    // where in the source it happened, if known
+    pub pos: Option<Pos>,
+}
+
+impl NumenError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> NumenError {
+        NumenError {
+            kind,
+            message: message.into(),
+            pos: None,
+        }
+    }
+
+    // sets the position unless a more precise one is already known
+    pub fn at(mut self, pos: &Pos) -> NumenError {
+        if self.pos.is_none() {
+            self.pos = Some(pos.clone());
+        }
+        self
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            ErrorKind::IO => "io error",
+            ErrorKind::IMPORT => "import error",
+            ErrorKind::SYNTAX => "syntax error",
+            ErrorKind::UNBALANCED => "unbalanced block",
+            ErrorKind::STACKUNDERFLOW => "stack underflow",
+            ErrorKind::TYPEMISMATCH => "type mismatch",
+            ErrorKind::INVALIDVALUE => "invalid value",
+            ErrorKind::UNDEFINED => "undefined name",
+            ErrorKind::OUTOFBOUNDS => "out of bounds",
+            ErrorKind::ARITHMETIC => "arithmetic error",
+            ErrorKind::ASSERTION => "assertion failed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::fmt::Display for NumenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if let Some(pos) = &self.pos {
+            write!(f, "{}: ", pos)?;
+        }
+        write!(f, "{} ({})", self.message, self.kind)
+    }
+}
+
+impl std::error::Error for NumenError {}
+
+// the error for a word that is no builtin, variable or function,
+// with the names closest to it as suggestions
+pub fn undefined_word<'a>(stage: &str, word: &str, names: impl Iterator<Item = &'a str>) -> NumenError {
+    // a typo changes about one letter in three
+    let limit = (word.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = names
+        .filter(|name| *name != word && *name != GLOBAL)
+        .map(|name| (distance(word, name), name))
+        .filter(|(dist, _)| *dist <= limit)
+        .collect();
+    close.sort();
+    close.dedup();
+    let mut message = format!("{}: {} is no builtin, variable or function", stage, word);
+    if !close.is_empty() {
+        let names: Vec<&str> = close.iter().take(3).map(|(_, name)| *name).collect();
+        message.push_str(&format!(", did you mean {}?", names.join(", ")));
+    }
+    NumenError::new(ErrorKind::UNDEFINED, message)
+}
+
+// the edits it takes to turn one word into the other
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb { diagonal } else { 1 + diagonal.min(above).min(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
@@ -1,19 +1,46 @@
-use crate::interpreter::array2string;
+use crate::bigint::BigInt;
+use crate::compiler::Quotation;
+use crate::interpreter::{array2string, map2string, quote2string, struct2string};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
 
 // global function name
-pub const GLOBAL: &'static str = "_global";
+pub const GLOBAL: &str = "_global";
 // main function name
-pub const MAIN: &'static str = "main";
+pub const MAIN: &str = "main";
 
-pub const TRUE: &'static str = "true";
-pub const FALSE: &'static str = "false";
+pub const TRUE: &str = "true";
+pub const FALSE: &str = "false";
+
+// words the interpreter runs itself
+pub const BUILTINS: [&str; 58] = [
+    "print", "assert", "swap", "drop", "clear", "rot", "copy", "carry", "big", "sqrt", "push", "pop", "fetch", "len",
+    "get", "set", "new", "has", "remove", "keys", "values", "call", "apply", "exec", "each", "map", "filter", "fold",
+    "band", "bor", "bxor", "bnot", "shl", "shr", "hex", "bin", "oct", "quot", "div", "rem", "mod", "pow",
+    "abs", "sign", "min", "max", "clamp", "floor", "ceil", "round", "trunc", "sin", "cos", "tan", "atan2", "exp",
+    "ln", "log10",
+];
+
+// the builtins looked up before variables and functions, the others come
+// after them, so a variable or function of the same name hides them
+pub const CORE: [&str; 13] = [
+    "print", "assert", "swap", "drop", "clear", "rot", "copy", "carry", "sqrt", "push", "pop", "fetch", "len",
+];
+
+// named floats, looked up last so variables and functions of the same name hide them
+pub const CONSTANTS: [(&str, f64); 2] = [("pi", std::f64::consts::PI), ("e", std::f64::consts::E)];
 
 #[derive(PartialEq, Clone, Debug, Copy)]
 pub enum TokId {
     FUNCTION,
+    STRUCT,
     IMPORT,
     END,
     AS,
+    // -- splits the parameters of a function from its results
+    EFFECT,
     RET,
     WHILE,
     DO,
@@ -23,9 +50,13 @@ pub enum TokId {
     MINUS,
     BLOCK,
     LOOP,
+    BREAK,
+    CONTINUE,
     MULTIPLY,
     DIVIDE,
     MOD,
+    // ** raises to a power
+    POWER,
     IS,
     ASSIGNMENT,
     RETURNINGASSIGNMENT,
@@ -34,63 +65,280 @@ pub enum TokId {
     SMALLER,
     BIGGEREQUALS,
     SMALLEREQUALS,
+    NOTEQUALS,
+    // logic on booleans, and and or also take a quotation for the right side
+    AND,
+    OR,
+    XOR,
+    NOT,
     STRING,
     BOOLEAN,
     INT,
+    BIGINT,
     FLOAT,
     ARRAYBEGIN,
     ARRAYEND,
     ARRAY,
+    MAPBEGIN,
+    MAPEND,
+    MAP,
+    INSTANCE,
+    QUOTEBEGIN,
+    QUOTEEND,
+    QUOTE,
+    // &name, a reference to the function name
+    FUNCREF,
     // raw types
     TINT,
+    TBIGINT,
     TFLOAT,
     TSTRING,
     TBOOL,
     TARRAY,
+    TMAP,
+    TQUOTE,
+    TFUNCREF,
+    // the type of a struct, pushed by its name
+    TSTRUCT,
+    // text of a ## comment, attached to the function after it
+    DOC,
     // for cool visualizations
     LINEBREAK,
     UNKNOWN,
 }
 
+// where a lexeme starts in the source, len is the number of chars it spans
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pos {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Lexeme<T> {
     pub id: TokId,
     pub rep: T,
+    pub pos: Pos,
 }
 
+// ints, floats and bools are kept as native values, strings and
+// names share their text through an Rc
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    STR(String),
+    INT(i64),
+    BIG(BigInt),
+    FLOAT(f64),
+    BOOL(bool),
+    STR(Rc<str>),
     ARR(Vec<Object>),
+    MAP(BTreeMap<Key, Object>),
+    // an instance with the values of its fields and the struct it belongs to
+    STRUCT(Rc<StructDef>, Vec<Object>),
+    DEF(Rc<StructDef>),
+    // a quotation, compiled once and shared by its copies, with the
+    // variables of the call it was pushed in once it is
+    QUOTE(Rc<Quotation>, Option<Rc<Closure>>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// the variables of a call, shared with the quotations pushed in it
+pub type Heap = Rc<RefCell<HashMap<String, Object>>>;
+
+// what a quotation sees wherever it runs, the variables of the call that pushed it
+pub struct Closure {
+    pub heap: Heap,
+    // variables of the if branches around it, as they were when it was pushed
+    pub scope: HashMap<String, Object>,
+    // pushed by the global code, so it assigns global variables
+    pub global: bool,
+}
+
+// quotations are equal when they are written the same, wherever they were pushed
+impl PartialEq for Closure {
+    fn eq(&self, _: &Closure) -> bool {
+        true
+    }
+}
+
+// the heap can hold the quotation itself
+impl std::fmt::Debug for Closure {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Closure")
+    }
+}
+
+// a struct declaration, struct Name field1 field2 end
+#[derive(Debug, PartialEq)]
+pub struct StructDef {
+    pub name: Rc<str>,
+    pub fields: Vec<Rc<str>>,
+}
+
+// values that can be map keys, a map is kept sorted by them so it
+// always goes through its keys in the same order, a bigint key is
+// only made for numbers that don't fit in an int
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Key {
+    BOOL(bool),
+    INT(i64),
+    BIG(BigInt),
+    STR(Rc<str>),
+}
+
+#[derive(Debug, Clone)]
 pub struct Object {
     pub id: TokId,
     pub rep: Value,
+    pub pos: Pos,
+}
+
+// objects are equal by value, wherever they were written, and an int
+// is equal to a bigint of the same number
+impl PartialEq for Object {
+    fn eq(&self, other: &Object) -> bool {
+        match (&self.rep, &other.rep) {
+            (Value::INT(i), Value::BIG(big)) | (Value::BIG(big), Value::INT(i)) => BigInt::from(*i) == *big,
+            _ => self.id == other.id && self.rep == other.rep,
+        }
+    }
+}
+
+// bools, then numbers by their value, then strings
+impl Ord for Key {
+    fn cmp(&self, other: &Key) -> Ordering {
+        match (self, other) {
+            (Key::BOOL(a), Key::BOOL(b)) => a.cmp(b),
+            (Key::INT(a), Key::INT(b)) => a.cmp(b),
+            (Key::BIG(a), Key::BIG(b)) => a.cmp(b),
+            (Key::INT(a), Key::BIG(b)) => BigInt::from(*a).cmp(b),
+            (Key::BIG(a), Key::INT(b)) => a.cmp(&BigInt::from(*b)),
+            (Key::STR(a), Key::STR(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Key {
+    fn rank(&self) -> u8 {
+        match self {
+            Key::BOOL(_) => 0,
+            Key::INT(_) | Key::BIG(_) => 1,
+            Key::STR(_) => 2,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Function {
     pub arguments: Vec<Object>,
     pub stack: Vec<Object>,
+    // what the function returns when it declares fun f a -- int as, None without --
+    pub results: Option<Vec<Object>>,
+    // lines of the ## comments written right before the function
+    pub doc: Vec<String>,
 }
 
 impl std::fmt::Display for TokId {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", *self as u32)
+        write!(f, "{:?}", self)
+    }
+}
+
+impl std::fmt::Display for Pos {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
     }
 }
 
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
+            Value::INT(i) => {
+                write!(f, "{}", i)
+            }
+            Value::BIG(big) => {
+                write!(f, "{}", big)
+            }
+            Value::FLOAT(fl) if fl.is_nan() => {
+                // like the literal
+                write!(f, "nan")
+            }
+            Value::FLOAT(fl) => {
+                // debug keeps the decimal point, so 1.0 doesn't print like an int
+                write!(f, "{:?}", fl)
+            }
+            Value::BOOL(b) => {
+                write!(f, "{}", b)
+            }
             Value::STR(s) => {
                 write!(f, "{}", s)
             }
             Value::ARR(arr) => {
                 write!(f, "{}", array2string(arr.clone()))
             }
+            Value::MAP(map) => {
+                write!(f, "{}", map2string(map))
+            }
+            Value::STRUCT(def, values) => {
+                write!(f, "{}", struct2string(def, values))
+            }
+            Value::DEF(def) => {
+                write!(f, "{}", def.name)
+            }
+            Value::QUOTE(quote, _) => {
+                write!(f, "{}", quote2string(quote))
+            }
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // a number of the type of its value, for the tests of other modules too
+    pub(crate) fn number(rep: Value) -> Object {
+        let id = match rep {
+            Value::BIG(_) => TokId::BIGINT,
+            Value::FLOAT(_) => TokId::FLOAT,
+            _ => TokId::INT,
+        };
+        Object { id, rep, pos: Pos::default() }
+    }
+
+    #[test]
+    fn ints_equal_bigints_of_the_same_number() {
+        assert_eq!(number(Value::INT(5)), number(Value::BIG(BigInt::from(5))));
+        assert_eq!(number(Value::BIG(BigInt::from(-5))), number(Value::INT(-5)));
+        assert_ne!(number(Value::INT(5)), number(Value::BIG(BigInt::from(6))));
+    }
+
+    #[test]
+    fn keys_order_numbers_by_value() {
+        let huge = BigInt::from(i64::MAX).pow(2);
+        let mut keys = vec![
+            Key::STR("a".into()),
+            Key::BIG(huge.clone()),
+            Key::INT(3),
+            Key::BIG(-&huge),
+            Key::BOOL(true),
+            Key::INT(-3),
+        ];
+        keys.sort();
+        assert_eq!(keys, vec![
+            Key::BOOL(true),
+            Key::BIG(-&huge),
+            Key::INT(-3),
+            Key::INT(3),
+            Key::BIG(huge),
+            Key::STR("a".into()),
+        ]);
+    }
+}
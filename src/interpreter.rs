@@ -1,1174 +1,1644 @@
-use crate::head::{Function, TokId, GLOBAL, MAIN, Object, Value, TRUE, FALSE};
-use std::collections::{HashMap};
-use std::process::{exit};
+use crate::bigint::BigInt;
+use crate::compiler::{compile, compile_stack, struct_types, Code, Instr, Op, Quotation};
+use crate::error::{undefined_word, ErrorKind, NumenError};
+use crate::head::{Closure, Function, Heap, Key, Pos, StructDef, TokId, BUILTINS, CONSTANTS, CORE, GLOBAL, MAIN, Object, Value};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
 
+const PARAM_MISMATCH: &str = "INTERP: error the type and value of the parameter don't match";
+// the most bits a power may have, squaring one this size already takes about a second
+const POWER_BITS: u64 = 1 << 20;
 
-pub fn interpret(mut function_map: HashMap<String, Function>) {
+pub fn interpret(program: HashMap<String, Code>) -> Result<(), NumenError> {
     let mut global_heap: HashMap<String, Object> = HashMap::new();
 
-    interpret_func(
-        &mut function_map,
-        GLOBAL.to_string(),
-        &mut global_heap,
-        None,
-        None,
-        None,
-    );
-    if function_map.contains_key(MAIN) {
-        interpret_func(
-            &mut function_map,
-            MAIN.to_string(),
-            &mut global_heap,
-            None,
-            None,
-            None,
-        );
+    for fname in [GLOBAL, MAIN] {
+        if let Some(code) = program.get(fname) {
+            Call::new(&program, fname, &mut global_heap, None).run(&code.instrs)?;
+        }
     }
+    Ok(())
 }
 
-fn interpret_func(
-    function_map: &mut HashMap<String, Function>,
-    fname: String,
-    global_heap: &mut HashMap<String, Object>,
-    parent_stack_option: Option<&mut Vec<Object>>,
-    custom_stack: Option<Vec<Object>>,
-    custom_heap: Option<&mut HashMap<String, Object>>,
-) -> Vec<Object> {
-    let Some(mut func) = function_map.get_mut(&fname).cloned() else {
-        panic!("INTERP: {} function does not exist", fname)
-    };
+// state kept alive between runs, used by the repl
+pub struct Session {
+    pub function_map: HashMap<String, Function>,
+    pub global_heap: HashMap<String, Object>,
+    pub stack: Vec<Object>,
+}
 
-    if custom_stack.is_some() {
-        func.stack = custom_stack.unwrap();
+impl Session {
+    pub fn new() -> Session {
+        let mut function_map: HashMap<String, Function> = HashMap::new();
+        function_map.insert(
+            GLOBAL.to_string(),
+            Function {
+                arguments: vec![],
+                stack: vec![],
+                results: None,
+                doc: vec![],
+            },
+        );
+        Session {
+            function_map,
+            global_heap: HashMap::new(),
+            stack: Vec::new(),
+        }
     }
 
-    let mut live_heap = &mut HashMap::new(); // HEAP
-    if custom_heap.is_some() {
-        live_heap = custom_heap.unwrap();
+    // runs code in the global scope on top of the current stack,
+    // the stack is left as it was if the code fails
+    pub fn run(&mut self, code: Vec<Object>) -> Result<(), NumenError> {
+        let program = compile(&self.function_map)?;
+        let instrs = compile_stack(&code, &struct_types(&self.function_map))?;
+        let mut call = Call::new(&program, GLOBAL, &mut self.global_heap, None);
+        call.live_stack = self.stack.clone();
+        call.run(&instrs)?;
+        self.stack = call.live_stack;
+        Ok(())
     }
+}
 
-    let mut live_stack: Vec<Object> = Vec::new(); // STACK
-    let mut parent_stack: Option<&mut Vec<Object>> = None;
+// a block with its own stack, like the body of a loop
+struct Frame {
+    // the stack of the code around the block
+    below: Vec<Object>,
+    // whether the values left in the block go back onto the stack below
+    merge: bool,
+    // if scopes opened before the block, they can't be seen inside of it
+    scope_base: usize,
+    // if scopes open when the block was entered, the ones after are closed on leave
+    scope_top: usize,
+}
 
-    if let Some(par_stack) = parent_stack_option {
-        // ARGUMENT PASSING
-        for (i, arg) in func.arguments.iter().enumerate() {
-            let value = par_stack.pop().unwrap_or_else(|| {
-                panic!(
-                    "Function {} expected {} arguments passed but got {}",
-                    fname,
-                    func.arguments.len(),
-                    i
-                )
-            });
-            if arg.id == TokId::UNKNOWN {
-                // variable name case
-                live_heap.insert(match arg.rep.clone() {
-                    Value::STR(s) => s,
-                    Value::ARR(_) => unreachable!()
-                }, value);
-            } else {
-                // type names, int float so on
-                match arg.id {
-                    TokId::TINT => {
-                        if value.id == TokId::INT {
-                            live_stack.push(value)
-                        } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                    }
-                    TokId::TFLOAT => {
-                        if value.id == TokId::FLOAT {
-                            live_stack.push(value)
-                        } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                    }
-                    TokId::TSTRING => {
-                        if value.id == TokId::STRING {
-                            live_stack.push(value)
-                        } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                    }
-                    TokId::TBOOL => {
-                        if value.id == TokId::BOOLEAN {
-                            live_stack.push(value)
-                        } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                    }
-                    TokId::TARRAY => {
-                        if value.id == TokId::ARRAY {
-                            live_stack.push(value)
-                        } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                    }
-                    _ => {
-                        panic!("INTERP: {} is not a name of a type", arg.id)
-                    }
-                }
-            }
+// everything a single function call works on
+struct Call<'a> {
+    program: &'a HashMap<String, Code>,
+    // the global code assigns global variables
+    global: bool,
+    global_heap: &'a mut HashMap<String, Object>,
+    parent_stack: Option<&'a mut Vec<Object>>,
+    live_heap: Heap,
+    live_stack: Vec<Object>,
+    frames: Vec<Frame>,
+    // variables of if and else branches
+    scopes: Vec<HashMap<String, Object>>,
+    // current and total count of the running loop statements
+    loops: Vec<(i64, i64)>,
+}
+
+// calls a function, its arguments are taken from the stack of the caller
+fn call_function(
+    program: &HashMap<String, Code>,
+    fname: &str,
+    global_heap: &mut HashMap<String, Object>,
+    par_stack: &mut Vec<Object>,
+) -> Result<(), NumenError> {
+    let code = program
+        .get(fname)
+        .ok_or_else(|| undefined_word("INTERP", fname, BUILTINS.iter().copied().chain(program.keys().map(String::as_str))))?;
+    let mut call = Call::new(program, fname, global_heap, None);
+    // ARGUMENT PASSING
+    for (i, arg) in code.arguments.iter().enumerate() {
+        let value = par_stack.pop().ok_or_else(|| NumenError::new(
+            ErrorKind::STACKUNDERFLOW,
+            format!("INTERP: Function {} expected {} arguments passed but got {}", fname, code.arguments.len(), i),
+        ))?;
+        if arg.id == TokId::UNKNOWN {
+            // variable name case
+            call.live_heap.borrow_mut().insert(cast2name(&arg.rep)?.to_string(), value);
+        } else {
+            // type names, int float so on
+            typecheck(arg, &value)?;
+            call.live_stack.push(value);
         }
-        parent_stack = Some(par_stack);
-    }
-
-    #[derive(PartialEq)]
-    enum BlockType {
-        NONE,
-        IF,
-        ELSE,
-    }
-    let mut block_types: Vec<BlockType> = Vec::new();
-    let mut vector_heap: Vec<HashMap<String, Object>> = Vec::new();
-    let mut block_level: i32 = -1;
-
-    let mut iter = func.stack.iter().enumerate();
-    'main: while let Some((_index, tok)) = iter.next() {
-        // blocking
-        if block_level > -1 {
-            if block_types[block_level as usize] == BlockType::IF {
-                if tok.id == TokId::ELSE {
-                    block_types[block_level as usize] = BlockType::NONE;
-                    vector_heap[block_level as usize].clear();
-                    block_level -= 1;
-                    // This will skip everything until end
-                    let mut item = iter.next().expect("INTERP: no argument to skip").1;
-                    let mut block_count = 0;
-                    while item.id != TokId::END || block_count != 0 {
-                        match item.id {
-                            TokId::IF | TokId::WHILE | TokId::BLOCK | TokId::LOOP => block_count += 1, //BLOCK CHECK
-                            TokId::END => block_count -= 1,
-                            _ => {}
-                        }
-                        item = iter
-                            .next()
-                            .expect("INTERP: 'end' is missing for the if statement")
-                            .1;
-                    }
-                    continue;
-                } else if tok.id == TokId::END {
-                    block_types[block_level as usize] = BlockType::NONE;
-                    vector_heap[block_level as usize].clear();
-                    block_level -= 1;
-                    continue;
-                }
-            } else if block_types[block_level as usize] == BlockType::ELSE {
-                if tok.id == TokId::END {
-                    block_types[block_level as usize] = BlockType::NONE;
-                    vector_heap[block_level as usize].clear();
-                    block_level -= 1;
-                    continue;
-                }
-            }
+    }
+    call.parent_stack = Some(par_stack);
+    call.run(&code.instrs)
+}
+
+impl<'a> Call<'a> {
+    fn new(
+        program: &'a HashMap<String, Code>,
+        fname: &'a str,
+        global_heap: &'a mut HashMap<String, Object>,
+        parent_stack: Option<&'a mut Vec<Object>>,
+    ) -> Call<'a> {
+        Call {
+            program,
+            global: fname == GLOBAL,
+            global_heap,
+            parent_stack,
+            live_heap: Heap::default(),
+            live_stack: Vec::new(),
+            frames: Vec::new(),
+            scopes: Vec::new(),
+            loops: Vec::new(),
         }
+    }
 
+    fn run(&mut self, instrs: &[Instr]) -> Result<(), NumenError> {
+        // errors that don't know where they happened get the instruction being run
+        let mut at = 0;
+        self.execute(instrs, &mut at).map_err(|err| match instrs.get(at) {
+            Some(instr) => err.at(&instr.pos),
+            None => err,
+        })
+    }
 
-        match tok.id {
-            TokId::LINEBREAK => {} // should not use linebreak
-            TokId::IMPORT => {}
-            TokId::LOOP => {
-                let times = live_stack.pop().expect("INTERP: no times argument for loop");
-                if times.id != TokId::INT {
-                    panic!("INTERP: can't evaluate times statement")
+    fn execute(&mut self, instrs: &[Instr], at: &mut usize) -> Result<(), NumenError> {
+        let mut pc = 0;
+        while let Some(instr) = instrs.get(pc) {
+            *at = pc;
+            pc += 1;
+            let pos = &instr.pos;
+            match &instr.op {
+                Op::PUSH(obj) => {
+                    let obj = match &obj.rep {
+                        Value::QUOTE(quote, None) => Object { rep: Value::QUOTE(quote.clone(), Some(self.closure())), ..obj.clone() },
+                        _ => obj.clone(),
+                    };
+                    self.live_stack.push(obj);
                 }
-
-                let mut loop_stack: Vec<Object> = Vec::new();
-                let mut block_count = 0;
-                let mut item = iter.next().expect("INTERP: no condition to evaluate for loop").1;
-
-                while item.id != TokId::END || block_count != 0 {
-                    match item.id {
-                        TokId::IF | TokId::WHILE | TokId::BLOCK | TokId::LOOP => block_count += 1, //BLOCK CHECK
-                        TokId::END => block_count -= 1,
-                        _ => {}
-                    }
-                    loop_stack.push(item.clone());
-                    item = iter.next().expect("INTERP: 'end' is missing for the loop statement").1;
+                Op::OPERATOR(id @ (TokId::AND | TokId::OR))
+                    if self.live_stack.last().is_some_and(|top| matches!(top.id, TokId::QUOTE | TokId::FUNCREF)) =>
+                {
+                    self.short_circuit(*id, pos)?
                 }
-                for it in 0..cast2int(cast2string(&times.rep)) {
-                    live_heap.insert("it".to_string(), Object { id: TokId::INT, rep: Value::STR(it.to_string()) });
-                    let mut runned_stack = interpret_func(
-                        function_map, fname.clone(), global_heap, parent_stack.as_deref_mut(),
-                        Some(loop_stack.clone()), Some(&mut live_heap),
-                    );
-                    while let Some(item) = runned_stack.pop() {
-                        live_stack.push(item)
+                Op::OPERATOR(id) => operator(*id, &mut self.live_stack, pos)?,
+                Op::WORD(name) => {
+                    if !(CORE.contains(&name.as_ref()) && builtin(name, &mut self.live_stack, pos)?) {
+                        self.word(name, pos)?;
                     }
                 }
-                live_heap.remove("it");
-            }
-            TokId::BLOCK => {
-                let mut as_params: Vec<Object> = Vec::new();
-                let mut let_stack: Vec<Object> = Vec::new();
-                let mut item = iter.next().expect("INTERP: no condition to evaluate for let").1;
-
-                while item.id != TokId::AS {
-                    as_params.push(item.clone());
-                    item = iter.next().expect("INTERP: 'as' is missing for the let statement").1;
+                Op::ASSIGN(name, keep) => {
+                    let popped = self.live_stack.pop().ok_or_else(|| underflow("INTERP: no argument to assign"))?;
+                    if *keep {
+                        self.live_stack.push(popped.clone());
+                    }
+                    self.assign(name, popped);
                 }
-                item = iter.next().expect("INTERP: no argument to evaluate for let ... as").1;
-
-                while let Some(item) = as_params.pop() {
-                    if item.id == TokId::UNKNOWN {
-                        let Value::STR(name) = &item.rep else {
-                            panic!("INTERP: parameter name is not a string");
-                        };
-                        live_heap.insert(name.to_string(), live_stack.pop().expect("INTERP: no item to parameterise for let"));
+                Op::IF(target) => {
+                    let condition = self.live_stack.pop().ok_or_else(|| underflow("INTERP: no condition argument for if"))?;
+                    if condition.id != TokId::BOOLEAN {
+                        return Err(mismatch(format!("INTERP: argument {} is not the type boolean", condition.id)))
+                    }
+                    if cast2bool(&condition.rep)? {
+                        self.scopes.push(HashMap::new());
                     } else {
-                        let Some(value) = live_stack.pop() else {
-                            panic!("INTERP: no item to parameterise for let");
-                        };
-                        match item.id {
-                            TokId::TINT => {
-                                if value.id == TokId::INT {
-                                    let_stack.push(value)
-                                } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                            }
-                            TokId::TFLOAT => {
-                                if value.id == TokId::FLOAT {
-                                    let_stack.push(value)
-                                } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                            }
-                            TokId::TSTRING => {
-                                if value.id == TokId::STRING {
-                                    let_stack.push(value)
-                                } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                            }
-                            TokId::TBOOL => {
-                                if value.id == TokId::BOOLEAN {
-                                    let_stack.push(value)
-                                } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                            }
-                            TokId::TARRAY => {
-                                if value.id == TokId::ARRAY {
-                                    let_stack.push(value)
-                                } else { panic!("INTERP: error the type and value of the parameter don't match") }
-                            }
-                            _ => {
-                                panic!("INTERP: {} is not a name of a type", item.id)
-                            }
-                        }
+                        pc = *target;
                     }
                 }
-                let_stack.reverse();
-                let mut block_count = 0;
-                while item.id != TokId::END || block_count != 0 {
-                    match item.id {
-                        TokId::IF | TokId::WHILE | TokId::BLOCK | TokId::LOOP => block_count += 1, //BLOCK CHECK
-                        TokId::END => block_count -= 1,
-                        _ => {}
-                    }
-                    let_stack.push(item.clone());
-                    item = iter.next().expect("INTERP: 'end' is missing for the let ... as statement").1;
+                Op::ELSE(target) => {
+                    self.scopes.pop();
+                    pc = *target;
                 }
-
-                let mut runned_stack = interpret_func(
-                    function_map, fname.clone(), global_heap, parent_stack.as_deref_mut(),
-                    Some(let_stack.clone()), Some(&mut live_heap),
-                );
-                while let Some(item) = runned_stack.pop() {
-                    live_stack.push(item)
+                Op::SCOPE => self.scopes.push(HashMap::new()),
+                Op::ENDSCOPE => {
+                    self.scopes.pop();
                 }
-            }
-            TokId::WHILE => {
-                let mut while_cond: Vec<Object> = Vec::new();
-                let mut do_stack: Vec<Object> = Vec::new();
-                let mut item = iter.next().expect("INTERP: no condition to evaluate for while").1;
-                let mut block_count = 0;
-                while item.id != TokId::DO || block_count != 0 {
-                    match item.id {
-                        TokId::IF | TokId::WHILE | TokId::BLOCK | TokId::LOOP => block_count += 1, //BLOCK CHECK
-                        TokId::END => block_count -= 1,
-                        _ => {}
-                    }
-                    while_cond.push(item.clone());
-                    item = iter.next().expect("INTERP: 'do' is missing for the while statement").1;
+                Op::ENTER(merge) => self.enter(Vec::new(), *merge),
+                Op::LEAVE => self.leave()?,
+                Op::LET(params) => {
+                    let let_stack = self.bind(params)?;
+                    self.enter(let_stack, true);
                 }
-                item = iter.next().expect("INTERP: no argument to evaluate for while ... do").1;
-                block_count = 0;
-                while item.id != TokId::END || block_count != 0 {
-                    match item.id {
-                        TokId::IF | TokId::WHILE | TokId::BLOCK | TokId::LOOP => block_count += 1, //BLOCK CHECK
-                        TokId::END => block_count -= 1,
-                        _ => {}
+                Op::LOOP(done) => {
+                    let times = self.live_stack.pop().ok_or_else(|| underflow("INTERP: no times argument for loop"))?;
+                    if times.id != TokId::INT {
+                        return Err(mismatch("INTERP: can't evaluate times statement"))
                     }
-                    do_stack.push(item.clone());
-                    item = iter.next().expect("INTERP: 'end' is missing for the while ... do statement").1;
-                }
-
-                let mut result = interpret_func(
-                    function_map, fname.clone(), global_heap, parent_stack.as_deref_mut(),
-                    Some(while_cond.clone()), Some(&mut live_heap),
-                );
-                let mut condition = result.pop().expect("INTERP: no condition for while");
-                while cast2string(&condition.rep) == TRUE {
-                    let mut runned_stack = interpret_func(
-                        function_map, fname.clone(), global_heap, parent_stack.as_deref_mut(),
-                        Some(do_stack.clone()), Some(&mut live_heap),
-                    );
-                    while let Some(item) = runned_stack.pop() {
-                        live_stack.push(item)
+                    let times = cast2int(&times.rep)?;
+                    self.loops.push((0, times));
+                    if times <= 0 {
+                        pc = *done;
                     }
-                    result = interpret_func(
-                        function_map, fname.clone(), global_heap, parent_stack.as_deref_mut(),
-                        Some(while_cond.clone()), Some(&mut live_heap),
-                    );
-                    condition = result.pop().expect("INTERP: no condition for while");
                 }
-            }
-            TokId::IF => {
-                let condition = live_stack.pop().expect("INTERP: no condition argument for if");
-                if condition.id != TokId::BOOLEAN {
-                    panic!("INTERP: argument {} is not the type boolean", condition.id)
+                Op::LOOPBODY => {
+                    let (it, _) = self.loops.last().ok_or_else(|| unbalanced("INTERP: loop body outside of a loop"))?;
+                    self.live_heap.borrow_mut().insert("it".to_string(), Object { id: TokId::INT, rep: Value::INT(*it), pos: pos.clone() });
+                    self.enter(Vec::new(), true);
                 }
-                if cast2string(&condition.rep) == TRUE {
-                    block_level += 1;
-                    block_types.push(BlockType::IF);
-                    vector_heap.insert(block_level as usize, HashMap::new());
-                    continue;
-                } else if cast2string(&condition.rep) == FALSE {
-                    let mut item = iter.next().expect("INTERP: no argument to evaluate for if").1;
-                    let mut block_count = 0;
-                    // This will skip everything until else or end
-                    while (item.id != TokId::END && item.id != TokId::ELSE) || block_count != 0 {
-                        match item.id {
-                            TokId::IF | TokId::WHILE | TokId::BLOCK | TokId::LOOP => block_count += 1, //BLOCK CHECK
-                            TokId::END => block_count -= 1,
-                            _ => {}
-                        }
-                        item = iter.next().expect("INTERP: 'end' is missing for the if statement").1;
-                    }
-                    if item.id == TokId::ELSE {
-                        block_level += 1;
-                        block_types.push(BlockType::ELSE);
-                        vector_heap.insert(block_level as usize, HashMap::new());
+                Op::LOOPNEXT(body) => {
+                    let (it, times) = self.loops.last_mut().ok_or_else(|| unbalanced("INTERP: loop body outside of a loop"))?;
+                    *it += 1;
+                    if *it < *times {
+                        pc = *body;
                     }
-                    continue;
-                } else {
-                    panic!("INTERP: condition {} is invalid", condition.id)
                 }
-            }
-            TokId::PLUS => {
-                let second = live_stack.pop().expect("INTERP: error no argument to add");
-                let first = live_stack.pop().expect("INTERP: error no argument to add");
-                match first.id {
-                    TokId::ARRAY => {
-                        if second.id == TokId::ARRAY {
-                            let Value::ARR(mut first_arr) = first.rep else {
-                                panic!("INTERP: expected Array but got this {}", first.rep);
-                            };
-                            let Value::ARR(second_arr) = second.rep else {
-                                panic!("INTERP: expected Array but got this {}", second.rep);
-                            };
-                            first_arr.extend_from_slice(&second_arr);
-                            live_stack.push(Object {
-                                id: TokId::ARRAY,
-                                rep: Value::ARR(first_arr),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be added", first, second);
-                        }
-                    }
-                    TokId::STRING => {
-                        //string
-                        if second.id == TokId::STRING {
-                            live_stack.push(Object {
-                                id: TokId::STRING,
-                                rep: Value::STR(cast2str(first.rep) + cast2str(second.rep).as_str()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be added", first, second);
-                        }
+                Op::LOOPDONE => {
+                    self.loops.pop();
+                    self.live_heap.borrow_mut().remove("it");
+                }
+                Op::WHILE(exit) => {
+                    let condition = self.live_stack.pop().ok_or_else(|| underflow("INTERP: no condition for while"))?;
+                    self.leave()?;
+                    if !cast2bool(&condition.rep)? {
+                        pc = *exit;
                     }
-                    TokId::INT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::INT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) + cast2int(cast2string(&second.rep))).to_string()), //Value::STR((cast2int(cast2string(&first.rep)) + cast2int(cast2string(&second.rep))).to_string())
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) as f64 + cast2float(cast2string(&second.rep)))
-                                    .to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be added", first, second);
-                        }
+                }
+                Op::JUMP(target) => pc = *target,
+                Op::RET => {
+                    if let Some(par_stack) = self.parent_stack.as_mut() {
+                        par_stack.push(
+                            self.live_stack
+                                .pop()
+                                .ok_or_else(|| underflow("INTERP: error no argument to return"))?,
+                        );
                     }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) + cast2int(cast2string(&second.rep)) as f64)
-                                    .to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) + cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be added", first, second);
-                        }
+                    while !self.frames.is_empty() {
+                        self.leave()?;
                     }
-                    typ => {
-                        panic!("INTERP: {:?} can't be added", typ);
+                    return Ok(());
+                }
+                Op::COLLECT => self.enter_literal(),
+                Op::ARRAYEND => {
+                    let items = std::mem::take(&mut self.live_stack);
+                    self.leave()?;
+                    self.live_stack.push(Object { id: TokId::ARRAY, rep: Value::ARR(items), pos: pos.clone() });
+                }
+                Op::MAPEND => {
+                    let items = std::mem::take(&mut self.live_stack);
+                    self.leave()?;
+                    self.live_stack.push(collect_map(items, pos)?);
+                }
+                Op::UNWIND(blocks, target) => {
+                    for _ in 0..*blocks {
+                        self.leave()?;
                     }
+                    pc = *target;
                 }
             }
-            TokId::MINUS => {
-                let second = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to subtract");
-                let first = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to subtract");
-                match first.id {
-                    TokId::INT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::INT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) - cast2int(cast2string(&second.rep))).to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) as f64 - cast2float(cast2string(&second.rep)))
-                                    .to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be subtracted", first, second);
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) - cast2int(cast2string(&second.rep)) as f64)
-                                    .to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) - cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be subtracted", first, second);
-                        }
-                    }
-                    typ => {
-                        panic!("INTERP: {:?} can't be subtracted", typ);
-                    }
+        }
+        Ok(())
+    }
+
+    // words that run quotations and function references, false if there is none
+    // with that name, a quotation runs on the stack of the call that runs it
+    fn quotation_word(&mut self, name: &str, pos: &Pos) -> Result<bool, NumenError> {
+        match name {
+            "call" | "apply" => {
+                let callable = self.pop_callable(name)?;
+                self.invoke(&callable)?;
+            }
+            "exec" => {
+                // dispatch by the name of a function in a string
+                let fname = self.live_stack.pop().ok_or_else(|| underflow("INTERP: error no function name to exec"))?;
+                let (TokId::STRING, Value::STR(fname)) = (fname.id, &fname.rep) else {
+                    return Err(mismatch(format!("INTERP: exec needs the name of a function but got {}", fname.rep)));
+                };
+                self.call_named(fname, pos)?;
+            }
+            "each" => {
+                // every item is pushed onto the stack before the quotation runs on it
+                let callable = self.pop_callable("each")?;
+                for item in self.pop_array("each")? {
+                    self.live_stack.push(item);
+                    self.invoke(&callable)?;
                 }
             }
-            TokId::MULTIPLY => {
-                let second = live_stack.pop().expect("INTERP: error no argument to multiply");
-                let first = live_stack.pop().expect("INTERP: error no argument to multiply");
-                match first.id {
-                    TokId::INT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::INT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) * cast2int(cast2string(&second.rep))).to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) as f64 * cast2float(cast2string(&second.rep)))
-                                    .to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be multiplied", first, second);
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) * cast2int(cast2string(&second.rep)) as f64)
-                                    .to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) * cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be multiplied", first, second);
-                        }
-                    }
-                    typ => {
-                        panic!("INTERP: {:?} can't be multiplied", typ);
-                    }
+            "map" => {
+                if !self.live_stack.last().is_some_and(|top| matches!(top.id, TokId::QUOTE | TokId::FUNCREF)) {
+                    self.live_stack.push(Object { id: TokId::TMAP, rep: Value::STR("map".into()), pos: pos.clone() });
+                    return Ok(true);
                 }
+                let callable = self.pop_callable("map")?;
+                let mut result: Vec<Object> = Vec::new();
+                for item in self.pop_array("map")? {
+                    result.push(self.run_on(&callable, vec![item], "map")?);
+                }
+                self.live_stack.push(Object { id: TokId::ARRAY, rep: Value::ARR(result), pos: pos.clone() });
             }
-            TokId::DIVIDE => {
-                let second = live_stack.pop().expect("INTERP: error no argument to divide");
-                let first = live_stack.pop().expect("INTERP: error no argument to divide");
-                match first.id {
-                    TokId::INT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::INT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) / cast2int(cast2string(&second.rep))).to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) as f64 / cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be divided", first, second);
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) / cast2int(cast2string(&second.rep)) as f64).to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) / cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be divided", first, second);
-                        }
+            "filter" => {
+                let callable = self.pop_callable("filter")?;
+                let mut result: Vec<Object> = Vec::new();
+                for item in self.pop_array("filter")? {
+                    let keep = self.run_on(&callable, vec![item.clone()], "filter")?;
+                    if keep.id != TokId::BOOLEAN {
+                        return Err(mismatch(format!("INTERP: the quotation of filter has to leave a boolean, not {}", keep.rep)));
                     }
-                    typ => {
-                        panic!("INTERP: {:?} can't be divided", typ);
+                    if cast2bool(&keep.rep)? {
+                        result.push(item);
                     }
                 }
+                self.live_stack.push(Object { id: TokId::ARRAY, rep: Value::ARR(result), pos: pos.clone() });
             }
-            TokId::MOD => {
-                let second = live_stack.pop().expect("INTERP: error no argument to mod");
-                let first = live_stack.pop().expect("INTERP: error no argument to mod");
-                match first.id {
-                    TokId::INT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::INT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) % cast2int(cast2string(&second.rep))).to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2int(cast2string(&first.rep)) as f64 % cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be used to mod", first, second);
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) % cast2int(cast2string(&second.rep)) as f64).to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT {
-                            live_stack.push(Object {
-                                id: TokId::FLOAT,
-                                rep: Value::STR((cast2float(cast2string(&first.rep)) % cast2float(cast2string(&second.rep))).to_string()),
-                            })
-                        } else {
-                            panic!("INTERP: {:?} and {:?} can't be used to mod", first, second);
-                        }
-                    }
-                    typ => {
-                        panic!("INTERP: {:?} can't be used to mod", typ);
-                    }
+            "fold" => {
+                // arr init ( + ) fold, the quotation gets the result so far and the next item
+                let callable = self.pop_callable("fold")?;
+                let mut acc = self.live_stack.pop().ok_or_else(|| underflow("INTERP: error no initial value to fold"))?;
+                for item in self.pop_array("fold")? {
+                    acc = self.run_on(&callable, vec![acc, item], "fold")?;
                 }
+                self.live_stack.push(acc);
             }
-            TokId::IS => {
-                let second = live_stack.pop().expect("INTERP: error no argument to typecheck");
-                let first = live_stack.pop().expect("INTERP: error no argument to typecheck");
-                if (first.id == TokId::INT && second.id == TokId::TINT)
-                    || (first.id == TokId::FLOAT && second.id == TokId::TFLOAT)
-                    || (first.id == TokId::BOOLEAN && second.id == TokId::TBOOL)
-                    || (first.id == TokId::STRING && second.id == TokId::TSTRING)
-                    || (first.id == TokId::ARRAY && second.id == TokId::TARRAY)
-                {
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // a ( b ) and, the quotation only runs when the left side doesn't decide alone
+    fn short_circuit(&mut self, id: TokId, pos: &Pos) -> Result<(), NumenError> {
+        let verb = if id == TokId::AND { "and" } else { "or" };
+        let callable = self.pop_callable(verb)?;
+        let first = self.live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", verb)))?;
+        let first = logical(&first, verb)?;
+        // false and, true or
+        if first != (id == TokId::AND) {
+            self.live_stack.push(boolean(first, pos));
+            return Ok(());
+        }
+        let second = self.run_on(&callable, Vec::new(), verb)?;
+        self.live_stack.push(boolean(logical(&second, verb)?, pos));
+        Ok(())
+    }
+
+    // a quotation or a function reference
+    fn pop_callable(&mut self, verb: &str) -> Result<Object, NumenError> {
+        let callable = self.live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no quotation to {}", verb)))?;
+        match callable.id {
+            TokId::QUOTE | TokId::FUNCREF => Ok(callable),
+            _ => Err(mismatch(format!("INTERP: {} needs a quotation or a function reference but got {}", verb, callable.rep))),
+        }
+    }
+
+    // a quotation runs on the current stack, a function takes its arguments from it
+    fn invoke(&mut self, callable: &Object) -> Result<(), NumenError> {
+        match &callable.rep {
+            Value::QUOTE(quote, Some(closure)) => self.run_closure(quote, closure),
+            Value::QUOTE(quote, None) => self.run(&quote.instrs),
+            Value::STR(fname) => self.call_named(fname, &callable.pos),
+            rep => Err(mismatch(format!("INTERP: {} can't be called", rep))),
+        }
+    }
+
+    // a function or builtin by its name, for &name and exec, in the order of word
+    fn call_named(&mut self, name: &str, pos: &Pos) -> Result<(), NumenError> {
+        if CORE.contains(&name) && builtin(name, &mut self.live_stack, pos)? {
+            return Ok(());
+        }
+        if !self.program.contains_key(name) && (self.quotation_word(name, pos)? || builtin(name, &mut self.live_stack, pos)?) {
+            return Ok(());
+        }
+        call_function(self.program, name, self.global_heap, &mut self.live_stack)
+    }
+
+    // the variables a quotation pushed now sees, the ones of if branches as they are
+    fn closure(&self) -> Rc<Closure> {
+        let base = self.scope_base();
+        Rc::new(Closure {
+            heap: self.live_heap.clone(),
+            scope: self.scopes[base..].iter().flatten().map(|(name, value)| (name.clone(), value.clone())).collect(),
+            global: self.global,
+        })
+    }
+
+    // runs a quotation with the variables it was pushed with instead of the ones of this call
+    fn run_closure(&mut self, quote: &Quotation, closure: &Closure) -> Result<(), NumenError> {
+        let scopes = if closure.scope.is_empty() { Vec::new() } else { vec![closure.scope.clone()] };
+        let heap = std::mem::replace(&mut self.live_heap, closure.heap.clone());
+        let scopes = std::mem::replace(&mut self.scopes, scopes);
+        let frames = std::mem::take(&mut self.frames);
+        let global = std::mem::replace(&mut self.global, closure.global);
+        let result = self.run(&quote.instrs);
+        self.live_heap = heap;
+        self.scopes = scopes;
+        self.frames = frames;
+        self.global = global;
+        result
+    }
+
+    fn pop_array(&mut self, verb: &str) -> Result<Vec<Object>, NumenError> {
+        let arr = self.live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no array to {}", verb)))?;
+        match arr.rep {
+            Value::ARR(arr) => Ok(arr),
+            rep => Err(mismatch(format!("INTERP: {} needs an array but got {}", verb, rep))),
+        }
+    }
+
+    // runs a quotation or function on a stack of its own and returns what it leaves on top
+    fn run_on(&mut self, callable: &Object, stack: Vec<Object>, verb: &str) -> Result<Object, NumenError> {
+        let below = std::mem::replace(&mut self.live_stack, stack);
+        let result = self.invoke(callable);
+        let mut stack = std::mem::replace(&mut self.live_stack, below);
+        result?;
+        stack.pop().ok_or_else(|| underflow(format!("INTERP: the quotation of {} left nothing on the stack", verb)))
+    }
+
+    // if scopes of the current block
+    fn scope_base(&self) -> usize {
+        self.frames.last().map_or(0, |frame| frame.scope_base)
+    }
+
+    fn enter(&mut self, stack: Vec<Object>, merge: bool) {
+        let below = std::mem::replace(&mut self.live_stack, stack);
+        self.frames.push(Frame {
+            below,
+            merge,
+            scope_base: self.scopes.len(),
+            scope_top: self.scopes.len(),
+        });
+    }
+
+    // array and map literals see the variables around them
+    fn enter_literal(&mut self) {
+        let below = std::mem::take(&mut self.live_stack);
+        self.frames.push(Frame {
+            below,
+            merge: false,
+            scope_base: self.scope_base(),
+            scope_top: self.scopes.len(),
+        });
+    }
+
+    fn leave(&mut self) -> Result<(), NumenError> {
+        let frame = self.frames.pop().ok_or_else(|| unbalanced("INTERP: no block to leave"))?;
+        let inner = std::mem::replace(&mut self.live_stack, frame.below);
+        if frame.merge {
+            // the values come back one by one from the top, so in reverse
+            self.live_stack.extend(inner.into_iter().rev());
+        }
+        self.scopes.truncate(frame.scope_top);
+        Ok(())
+    }
+
+    // binds the parameters of a let, named ones become variables and
+    // typed ones start the stack of the block
+    fn bind(&mut self, params: &[Object]) -> Result<Vec<Object>, NumenError> {
+        let mut let_stack: Vec<Object> = Vec::new();
+        for param in params.iter().rev() {
+            let value = self.live_stack.pop().ok_or_else(|| underflow("INTERP: no item to parameterise for let"))?;
+            if param.id == TokId::UNKNOWN {
+                self.live_heap.borrow_mut().insert(cast2name(&param.rep)?.to_string(), value);
+            } else {
+                typecheck(param, &value)?;
+                let_stack.push(value);
+            }
+        }
+        let_stack.reverse();
+        Ok(let_stack)
+    }
+
+    // variable casting or function call
+    fn word(&mut self, def: &str, pos: &Pos) -> Result<(), NumenError> {
+        let base = self.scope_base();
+        let local = self.live_heap.borrow().get(def).cloned();
+        if let Some(value) = self.scopes[base..].iter().rev().find_map(|scope| scope.get(def)) {
+            // INSIDE A BLOCK
+            self.live_stack.push(value.clone());
+        } else if let Some(value) = local {
+            // LOCAL VARIABLE
+            self.live_stack.push(value);
+        } else if let Some(value) = self.global_heap.get(def) {
+            // GLOBAL VARIABLE
+            self.live_stack.push(value.clone());
+        } else if self.program.contains_key(def) {
+            //FUNCTION CALL
+            call_function(self.program, def, self.global_heap, &mut self.live_stack)?;
+        } else if self.quotation_word(def, pos)? || builtin(def, &mut self.live_stack, pos)? {
+            // A LATER BUILTIN
+        } else if let Some((_, value)) = CONSTANTS.iter().find(|(name, _)| *name == def) {
+            self.live_stack.push(Object { id: TokId::FLOAT, rep: Value::FLOAT(*value), pos: pos.clone() });
+        } else {
+            let heap = self.live_heap.borrow();
+            let names = BUILTINS
+                .iter()
+                .copied()
+                .chain(CONSTANTS.iter().map(|(name, _)| *name))
+                .chain(self.scopes[base..].iter().flat_map(|scope| scope.keys()).map(String::as_str))
+                .chain(heap.keys().chain(self.global_heap.keys()).chain(self.program.keys()).map(String::as_str));
+            return Err(undefined_word("INTERP", def, names));
+        }
+        Ok(())
+    }
+
+    fn assign(&mut self, name: &str, popped: Object) {
+        let base = self.scope_base();
+        let local = self.live_heap.borrow().contains_key(name);
+        if self.global {
+            self.global_heap.insert(name.to_string(), popped);
+        } else if self.scopes.len() > base {
+            // INSIDE A BLOCK
+            if let Some(slot) = self.global_heap.get_mut(name) {
+                *slot = popped;
+            } else if local {
+                self.live_heap.borrow_mut().insert(name.to_string(), popped);
+            } else if let Some(slot) = self.scopes[base..].iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+                *slot = popped;
+            } else if let Some(scope) = self.scopes.last_mut() {
+                scope.insert(name.to_string(), popped);
+            }
+        } else if let Some(slot) = self.global_heap.get_mut(name) {
+            *slot = popped;
+        } else {
+            self.live_heap.borrow_mut().insert(name.to_string(), popped);
+        }
+    }
+}
+
+// arithmetic, comparisons and is
+fn operator(id: TokId, live_stack: &mut Vec<Object>, pos: &Pos) -> Result<(), NumenError> {
+    match id {
+        TokId::PLUS => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to add"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to add"))?;
+            match (first.rep, second.rep) {
+                (Value::ARR(mut first_arr), Value::ARR(second_arr)) => {
+                    first_arr.extend(second_arr);
                     live_stack.push(Object {
-                        id: TokId::BOOLEAN,
-                        rep: Value::STR(TRUE.to_string()),
-                    });
-                } else {
+                        id: TokId::ARRAY,
+                        rep: Value::ARR(first_arr),
+                        pos: pos.clone(),
+                    })
+                }
+                (Value::STR(first_str), Value::STR(second_str))
+                    if first.id == TokId::STRING && second.id == TokId::STRING =>
+                {
                     live_stack.push(Object {
-                        id: TokId::BOOLEAN,
-                        rep: Value::STR(FALSE.to_string()),
-                    });
+                        id: TokId::STRING,
+                        rep: Value::STR(format!("{}{}", first_str, second_str).into()),
+                        pos: pos.clone(),
+                    })
+                }
+                (first_rep, second_rep) => {
+                    live_stack.push(arithmetic(first_rep, second_rep, "added", i64::checked_add, |a, b| Some(a + b), |a, b| Some(a + b), pos)?)
                 }
             }
-            TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT => {
-                let Some((_, mut var)) = iter.next() else {
-                    panic!("INTERP: no variable name to assign in to");
-                };
-                if var.id == TokId::LINEBREAK {
-                    var = iter.next().expect("INTERP: no variable to assign in to").1
+        }
+        TokId::MINUS => {
+            let second = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to subtract"))?;
+            let first = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to subtract"))?;
+            live_stack.push(arithmetic(first.rep, second.rep, "subtracted", i64::checked_sub, |a, b| Some(a - b), |a, b| Some(a - b), pos)?)
+        }
+        TokId::MULTIPLY => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to multiply"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to multiply"))?;
+            live_stack.push(arithmetic(first.rep, second.rep, "multiplied", i64::checked_mul, |a, b| Some(a * b), |a, b| Some(a * b), pos)?)
+        }
+        TokId::DIVIDE => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to divide"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to divide"))?;
+            live_stack.push(divide("/", first.rep, second.rep, pos)?)
+        }
+        TokId::MOD => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to mod"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to mod"))?;
+            live_stack.push(divide("%", first.rep, second.rep, pos)?)
+        }
+        TokId::POWER => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to raise"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to raise"))?;
+            live_stack.push(power(first.rep, second.rep, pos)?)
+        }
+        TokId::IS => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to typecheck"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to typecheck"))?;
+            live_stack.push(boolean(is_type(&first, &second), pos));
+        }
+        TokId::EQUALS => {
+            let second = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to check for equation"))?;
+            let first = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to check for equation"))?;
+            live_stack.push(boolean(first == second, pos));
+        }
+        TokId::BIGGER => {
+            let second = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for bigger"))?;
+            let first = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for bigger"))?;
+            let order = compare(&first.rep, &second.rep, "bigger")?;
+            live_stack.push(boolean(order == Some(Ordering::Greater), pos));
+        }
+        TokId::SMALLER => {
+            let second = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for smaller"))?;
+            let first = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for smaller"))?;
+            let order = compare(&first.rep, &second.rep, "smaller")?;
+            live_stack.push(boolean(order == Some(Ordering::Less), pos));
+        }
+        TokId::BIGGEREQUALS => {
+            let second = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for bigger equals"))?;
+            let first = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for bigger equals"))?;
+            let order = compare(&first.rep, &second.rep, "bigger equals")?;
+            live_stack.push(boolean(matches!(order, Some(Ordering::Greater | Ordering::Equal)), pos));
+        }
+        TokId::SMALLEREQUALS => {
+            let second = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for smaller equals"))?;
+            let first = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to compare for smaller equals"))?;
+            let order = compare(&first.rep, &second.rep, "smaller equals")?;
+            live_stack.push(boolean(matches!(order, Some(Ordering::Less | Ordering::Equal)), pos));
+        }
+        TokId::NOTEQUALS => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to check for inequality"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to check for inequality"))?;
+            live_stack.push(boolean(first != second, pos));
+        }
+        TokId::AND | TokId::OR | TokId::XOR => {
+            let verb = match id {
+                TokId::AND => "and",
+                TokId::OR => "or",
+                _ => "xor",
+            };
+            let second = live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", verb)))?;
+            let first = live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", verb)))?;
+            let (first, second) = (logical(&first, verb)?, logical(&second, verb)?);
+            let result = match id {
+                TokId::AND => first && second,
+                TokId::OR => first || second,
+                _ => first != second,
+            };
+            live_stack.push(boolean(result, pos));
+        }
+        TokId::NOT => {
+            let item = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to not"))?;
+            live_stack.push(boolean(!logical(&item, "not")?, pos));
+        }
+        _ => {
+            return Err(NumenError::new(ErrorKind::UNDEFINED, format!("INTERP: {} is not an operator", id)))
+        }
+    }
+    Ok(())
+}
+
+// runs a builtin word, false if there is none with that name
+fn builtin(name: &str, live_stack: &mut Vec<Object>, pos: &Pos) -> Result<bool, NumenError> {
+    match name {
+        "print" => {
+            let value = live_stack
+                .pop()
+                .ok_or_else(|| underflow("INTERP: error no argument to print"))?;
+            match value.id {
+                TokId::STRING | TokId::INT | TokId::BIGINT | TokId::FLOAT | TokId::BOOLEAN | TokId::ARRAY | TokId::MAP | TokId::INSTANCE | TokId::QUOTE => {
+                    println!("{}", value.rep)
                 }
-                if var.id != TokId::UNKNOWN {
-                    panic!("INTERP: {} -> {} is not a variable name", var.id, var.rep)
+                TokId::FUNCREF => println!("&{}", value.rep),
+                _ => {
+                    return Err(mismatch(format!("INTERP: can't print {}", value.rep)));
                 }
-                let popped = live_stack.pop().expect("INTERP: no argument to assign");
-                if tok.id == TokId::RETURNINGASSIGNMENT {
-                    live_stack.push(popped.clone());
+            }
+        }
+        "assert" => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to assert"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to assert"))?;
+            if first != second {
+                return Err(NumenError::new(
+                    ErrorKind::ASSERTION,
+                    format!("INTERP: \"{}\" != \"{}\"", first.rep, second.rep),
+                ));
+            }
+        }
+        "swap" => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to swap"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to swap"))?;
+            live_stack.push(second);
+            live_stack.push(first);
+        }
+        "drop" => {
+            live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to drop"))?;
+        }
+        "clear" => { //risky! clears the entire stack
+            live_stack.clear();
+        }
+        "rot" => {
+            let third = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to rot"))?;
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to rot"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to rot"))?;
+            live_stack.push(third);
+            live_stack.push(first);
+            live_stack.push(second);
+        }
+        "copy" => {
+            let top = live_stack.last().ok_or_else(|| underflow("INTERP: error no argument to copy"))?;
+            live_stack.push(top.clone());
+        }
+        "carry" => {
+            let second = live_stack
+                .len()
+                .checked_sub(2)
+                .and_then(|i| live_stack.get(i))
+                .ok_or_else(|| underflow("INTERP: error no argument to carry"))?;
+            live_stack.push(second.clone());
+        }
+        "big" => {
+            // turns an int into a bigint, so it can grow past 64 bits
+            let item = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to make big"))?;
+            let big = cast2big(&item.rep).ok_or_else(|| mismatch(format!("INTERP: {} can't be made a bigint", item.rep)))?;
+            live_stack.push(Object {
+                id: TokId::BIGINT,
+                rep: Value::BIG(big),
+                pos: pos.clone(),
+            });
+        }
+        // bitwise words, on the 64 bits of an int
+        "band" | "bor" | "bxor" => {
+            let second = pop_int(live_stack, name)?;
+            let first = pop_int(live_stack, name)?;
+            let result = match name {
+                "band" => first & second,
+                "bor" => first | second,
+                _ => first ^ second,
+            };
+            live_stack.push(Object { id: TokId::INT, rep: Value::INT(result), pos: pos.clone() });
+        }
+        "bnot" => {
+            let item = pop_int(live_stack, name)?;
+            live_stack.push(Object { id: TokId::INT, rep: Value::INT(!item), pos: pos.clone() });
+        }
+        "shl" | "shr" => {
+            // bits shifted out are gone, shr keeps the sign
+            let by = pop_int(live_stack, name)?;
+            let item = pop_int(live_stack, name)?;
+            let by = u32::try_from(by).ok().filter(|by| *by < 64).ok_or_else(|| NumenError::new(
+                ErrorKind::ARITHMETIC,
+                format!("INTERP: can't shift by {}, only by 0 to 63", by),
+            ))?;
+            let result = if name == "shl" { item << by } else { item >> by };
+            live_stack.push(Object { id: TokId::INT, rep: Value::INT(result), pos: pos.clone() });
+        }
+        "quot" | "div" | "rem" | "mod" | "pow" => {
+            let second = live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", name)))?;
+            let first = live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", name)))?;
+            live_stack.push(match name {
+                "pow" => power(first.rep, second.rep, pos)?,
+                _ => divide(name, first.rep, second.rep, pos)?,
+            });
+        }
+        // an int as a string that reads back as the same literal
+        "hex" | "bin" | "oct" => {
+            let item = pop_int(live_stack, name)?;
+            let sign = if item < 0 { "-" } else { "" };
+            let digits = match name {
+                "hex" => format!("0x{:x}", item.unsigned_abs()),
+                "bin" => format!("0b{:b}", item.unsigned_abs()),
+                _ => format!("0o{:o}", item.unsigned_abs()),
+            };
+            live_stack.push(Object { id: TokId::STRING, rep: Value::STR(format!("{}{}", sign, digits).into()), pos: pos.clone() });
+        }
+        "push" => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to push"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to push"))?;
+            if first.id == TokId::ARRAY {
+                match second.id {
+                    TokId::INT | TokId::BIGINT | TokId::FLOAT | TokId::BOOLEAN | TokId::ARRAY | TokId::MAP | TokId::INSTANCE | TokId::QUOTE | TokId::FUNCREF | TokId::STRING => { // Base Types
+                        let Value::ARR(mut first_arr) = first.rep else {
+                            return Err(mismatch(format!("INTERP: expected Array but got this {}", first.rep)));
+                        };
+                        first_arr.push(second);
+                        live_stack.push(Object {
+                            id: TokId::ARRAY,
+                            rep: Value::ARR(first_arr),
+                            pos: pos.clone(),
+                        })
+                    }
+                    _ => return Err(mismatch(format!("INTERP: {} can't be pushed into {}", second.rep, first.rep)))
                 }
-                if fname == GLOBAL {
-                    global_heap.insert(cast2str(var.rep.clone()), popped);
-                } else {
-                    if block_level > -1 {
-                        // INSIDE A BLOCK
-                        if global_heap.contains_key(cast2string(&var.rep)) {
-                            global_heap.insert(cast2str(var.rep.clone()), popped);
-                        } else if live_heap.contains_key(cast2string(&var.rep)) {
-                            live_heap.insert(cast2str(var.rep.clone()), popped);
-                        } else {
-                            for i in (0..block_level + 1).rev() {
-                                if vector_heap[i as usize].contains_key(cast2string(&var.rep)) {
-                                    vector_heap[i as usize].insert(cast2str(var.rep.clone()), popped);
-                                    continue 'main;
-                                }
-                            }
-                            vector_heap[block_level as usize].insert(cast2str(var.rep.clone()), popped);
-                        }
-                    } else {
-                        if global_heap.contains_key(cast2string(&var.rep)) {
-                            global_heap.insert(cast2str(var.rep.clone()), popped);
-                        } else {
-                            live_heap.insert(cast2str(var.rep.clone()), popped);
-                        }
+            } else if second.id == TokId::ARRAY {
+                match first.id {
+                    TokId::INT | TokId::BIGINT | TokId::FLOAT | TokId::BOOLEAN | TokId::ARRAY | TokId::MAP | TokId::INSTANCE | TokId::QUOTE | TokId::FUNCREF | TokId::STRING => { // Base Types
+                        let Value::ARR(mut second_arr) = second.rep else {
+                            return Err(mismatch(format!("INTERP: expected Array but got this {}", first.rep)));
+                        };
+                        second_arr.push(first);
+                        live_stack.push(Object {
+                            id: TokId::ARRAY,
+                            rep: Value::ARR(second_arr),
+                            pos: pos.clone(),
+                        })
                     }
+                    _ => return Err(mismatch(format!("INTERP: {} can't be pushed into {}", first.rep, second.rep)))
                 }
+            } else {
+                return Err(mismatch("INTERP: no Array provided for push"))
             }
-            TokId::EQUALS => {
-                let second = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to check for equation");
-                let first = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to check for equation");
-                if first == second {
-                    live_stack.push(Object {
-                        id: TokId::BOOLEAN,
-                        rep: Value::STR(TRUE.to_string()),
-                    });
-                } else {
+        }
+        "pop" => {
+            let item = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to pop"))?;
+            if item.id == TokId::ARRAY {
+                let Value::ARR(mut arr) = item.rep else {
+                    return Err(mismatch(format!("INTERP: expected Array but got this {}", item.rep)));
+                };
+                if let Some(popped) = arr.pop() {
                     live_stack.push(Object {
-                        id: TokId::BOOLEAN,
-                        rep: Value::STR(FALSE.to_string()),
+                        id: TokId::ARRAY,
+                        rep: Value::ARR(arr),
+                        pos: pos.clone(),
                     });
+                    live_stack.push(popped)
                 }
             }
-            TokId::BIGGER => {
-                let second = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for bigger");
-                let first = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for bigger");
-                match first.id {
+        }
+        "fetch" => {
+            let second = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to fetch"))?;
+            let first = live_stack.pop().ok_or_else(|| underflow("INTERP: error no argument to fetch"))?;
+            if first.id == TokId::ARRAY {
+                match second.id {
                     TokId::INT => {
-                        if second.id == TokId::INT && cast2int(cast2string(&first.rep)) > cast2int(cast2string(&second.rep)) {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2int(cast2string(&first.rep)) as f64 > cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT
-                            && cast2float(cast2string(&first.rep)) > cast2int(cast2string(&second.rep)) as f64
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2float(cast2string(&first.rep)) > cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
+                        let Value::ARR(first_arr) = first.rep else {
+                            return Err(mismatch(format!("INTERP: expected Array but got this {}", first.rep)));
+                        };
+                        let mut index = cast2int(&second.rep)?;
+                        if index < 0 {
+                            index += first_arr.len() as i64;
                         }
+                        live_stack.push(
+                            usize::try_from(index).ok().and_then(|i| first_arr.get(i)).ok_or_else(|| NumenError::new(ErrorKind::OUTOFBOUNDS, "INTERP: array out of bounds"))?.clone()
+                        )
                     }
-                    typ => {
-                        panic!("INTERP: {:?} can't be compared for bigger", typ);
-                    }
+                    _ => return Err(mismatch(format!("INTERP: {} can't be indexed by {}", second.rep, first.rep)))
                 }
-            }
-            TokId::SMALLER => {
-                let second = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for smaller");
-                let first = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for smaller");
+            } else if second.id == TokId::ARRAY {
                 match first.id {
                     TokId::INT => {
-                        if second.id == TokId::INT && cast2int(cast2string(&first.rep)) < cast2int(cast2string(&second.rep)) {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2float(cast2string(&second.rep)) > cast2int(cast2string(&first.rep)) as f64
-                        {
-                            // hack because other way don't work
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT
-                            && cast2float(cast2string(&first.rep)) < cast2int(cast2string(&second.rep)) as f64
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2float(cast2string(&first.rep)) < cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
+                        let Value::ARR(second_arr) = second.rep else {
+                            return Err(mismatch(format!("INTERP: expected Array but got this {}", second.rep)));
+                        };
+                        let mut index = cast2int(&first.rep)?;
+                        if index < 0 {
+                            index += second_arr.len() as i64;
                         }
+                        live_stack.push(
+                            usize::try_from(index).ok().and_then(|i| second_arr.get(i)).ok_or_else(|| NumenError::new(ErrorKind::OUTOFBOUNDS, "INTERP: array out of bounds"))?.clone()
+                        )
                     }
-                    typ => {
-                        panic!("INTERP: {:?} can't be compared for smaller", typ);
-                    }
+                    _ => return Err(mismatch(format!("INTERP: {} can't be indexed by {}", first.rep, second.rep)))
                 }
+            } else {
+                return Err(mismatch("INTERP: no Array provided for fetch"))
             }
-            TokId::BIGGEREQUALS => {
-                let second = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for bigger equals");
-                let first = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for bigger equals");
-                match first.id {
-                    TokId::INT => {
-                        if second.id == TokId::INT && cast2int(cast2string(&first.rep)) >= cast2int(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2int(cast2string(&first.rep)) as f64 >= cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT
-                            && cast2float(cast2string(&first.rep)) >= cast2int(cast2string(&second.rep)) as f64
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2float(cast2string(&first.rep)) >= cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
-                        }
-                    }
-                    typ => {
-                        panic!("INTERP: {:?} can't be compared for bigger equals", typ);
-                    }
+        }
+        "len" => {
+            let item = live_stack.last().ok_or_else(|| underflow("INTERP: error no argument to get length of"))?;
+            match item.id {
+                TokId::ARRAY => {
+                    let Value::ARR(arr) = &item.rep else {
+                        return Err(mismatch(format!("INTERP: expected Array but got this {}", item.rep)));
+                    };
+                    live_stack.push(Object {
+                        id: TokId::INT,
+                        rep: Value::INT(arr.len() as i64),
+                        pos: pos.clone(),
+                    })
                 }
-            }
-            TokId::SMALLEREQUALS => {
-                let second = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for smaller equals");
-                let first = live_stack
-                    .pop()
-                    .expect("INTERP: error no argument to compare for smaller equals");
-                match first.id {
-                    TokId::INT => {
-                        if second.id == TokId::INT && cast2int(cast2string(&first.rep)) <= cast2int(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2int(cast2string(&first.rep)) as f64 <= cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
-                        }
-                    }
-                    TokId::FLOAT => {
-                        if second.id == TokId::INT
-                            && cast2float(cast2string(&first.rep)) <= cast2int(cast2string(&second.rep)) as f64
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else if second.id == TokId::FLOAT
-                            && cast2float(cast2string(&first.rep)) <= cast2float(cast2string(&second.rep))
-                        {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(TRUE.to_string()),
-                            })
-                        } else {
-                            live_stack.push(Object {
-                                id: TokId::BOOLEAN,
-                                rep: Value::STR(FALSE.to_string()),
-                            })
-                        }
-                    }
-                    typ => {
-                        panic!("INTERP: {:?} can't be compared for smaller equals", typ);
-                    }
+                TokId::MAP => {
+                    let Value::MAP(map) = &item.rep else {
+                        return Err(mismatch(format!("INTERP: expected Map but got this {}", item.rep)));
+                    };
+                    live_stack.push(Object {
+                        id: TokId::INT,
+                        rep: Value::INT(map.len() as i64),
+                        pos: pos.clone(),
+                    })
                 }
+                _ => live_stack.push(Object {
+                    id: TokId::INT,
+                    rep: Value::INT(0),
+                    pos: pos.clone(),
+                })
             }
-            TokId::RET => {
-                if let Some(par_stack) = parent_stack.as_mut() {
-                    par_stack.push(
-                        live_stack
-                            .pop()
-                            .expect("INTERP: error no argument to return"),
-                    );
-                }
+        }
+        "get" => {
+            let key = live_stack.pop().ok_or_else(|| underflow("INTERP: error no key to get"))?;
+            let map = live_stack.pop().ok_or_else(|| underflow("INTERP: error no map to get from"))?;
+            if let Value::STRUCT(def, mut values) = map.rep {
+                let i = field_index(&def, &key)?;
+                live_stack.push(values.swap_remove(i));
+                return Ok(true);
             }
-            TokId::UNKNOWN => {
-                match cast2string(&tok.rep).as_str() {
-                    "print" => {
-                        let value = live_stack
-                            .pop()
-                            .expect("INTERP: error no argument to print");
-                        match value.id {
-                            TokId::STRING | TokId::INT | TokId::FLOAT | TokId::BOOLEAN => {
-                                println!("{}", value.rep)
-                            }
-                            TokId::ARRAY => {
-                                match value.rep {
-                                    Value::STR(sr) => {
-                                        panic!("INTERP: argument defined as Array is not an Array {}", sr)
-                                    }
-                                    Value::ARR(arr) => {
-                                        println!("{}", array2string(arr))
-                                    }
-                                }
-                            }
-                            _ => {
-                                panic!("INTERP: can't print {}", value.rep);
-                            }
-                        }
-                    }
-                    "assert" => {
-                        let second = live_stack.pop().expect("INTERP: error no argument to assert");
-                        let first = live_stack.pop().expect("INTERP: error no argument to assert");
-                        if first != second {
-                            println!("\"{}\" != \"{}\"", first.rep, second.rep);
-                            exit(1);
-                        }
-                    }
-                    "swap" => {
-                        let second = live_stack.pop().expect("INTERP: error no argument to swap");
-                        let first = live_stack.pop().expect("INTERP: error no argument to swap");
-                        live_stack.push(second);
-                        live_stack.push(first);
-                    }
-                    "drop" => {
-                        live_stack.pop().expect("INTERP: error no argument to drop");
-                    }
-                    "clear" => { //risky! clears the entire stack
-                        live_stack.clear();
-                    }
-                    "rot" => {
-                        let third = live_stack.pop().expect("INTERP: error no argument to rot");
-                        let second = live_stack.pop().expect("INTERP: error no argument to rot");
-                        let first = live_stack.pop().expect("INTERP: error no argument to rot");
-                        live_stack.push(third);
-                        live_stack.push(first);
-                        live_stack.push(second);
-                    }
-                    "copy" => {
-                        let top = live_stack.last().expect("INTERP: error no argument to rot");
-                        live_stack.push(top.clone());
-                    }
-                    "carry" => {
-                        let second = live_stack
-                            .get(live_stack.len() - 2)
-                            .expect("INTERP: error no argument to rot");
-                        live_stack.push(second.clone());
-                    }
-                    "sqrt" => {
-                        let item = live_stack
-                            .pop()
-                            .expect("INTERP: error no argument to take square of");
-                        match item.id {
-                            TokId::INT => {
-                                live_stack.push(Object {
-                                    rep: Value::STR((f64::sqrt(cast2int(cast2string(&item.rep)) as f64) as i32).to_string()),
-                                    id: TokId::INT,
-                                });
-                            }
-                            TokId::FLOAT => {
-                                live_stack.push(Object {
-                                    rep: Value::STR((f64::sqrt(cast2float(cast2string(&item.rep)))).to_string()),
-                                    id: TokId::FLOAT,
-                                });
-                            }
-                            typ => {
-                                panic!("INTERP: {:?} can't take the square root of this type", typ);
-                            }
-                        }
-                    }
-                    "push" => {
-                        let second = live_stack.pop().expect("INTERP: error no argument to push");
-                        let first = live_stack.pop().expect("INTERP: error no argument to push");
-                        if first.id == TokId::ARRAY {
-                            match second.id {
-                                TokId::INT | TokId::FLOAT | TokId::BOOLEAN | TokId::ARRAY | TokId::STRING => { // Base Types
-                                    let Value::ARR(mut first_arr) = first.rep else {
-                                        panic!("INTERP: expected Array but got this {}", first.rep);
-                                    };
-                                    first_arr.push(second);
-                                    live_stack.push(Object {
-                                        id: TokId::ARRAY,
-                                        rep: Value::ARR(first_arr),
-                                    })
-                                }
-                                _ => panic!("INTERP: {} can't be pushed into {}", second.rep, first.rep)
-                            }
-                        } else if second.id == TokId::ARRAY {
-                            match first.id {
-                                TokId::INT | TokId::FLOAT | TokId::BOOLEAN | TokId::ARRAY | TokId::STRING => { // Base Types
-                                    let Value::ARR(mut second_arr) = second.rep else {
-                                        panic!("INTERP: expected Array but got this {}", first.rep);
-                                    };
-                                    second_arr.push(first);
-                                    live_stack.push(Object {
-                                        id: TokId::ARRAY,
-                                        rep: Value::ARR(second_arr),
-                                    })
-                                }
-                                _ => panic!("INTERP: {} can't be pushed into {}", first.rep, second.rep)
-                            }
-                        } else {
-                            panic!("INTERP: no Array provided for push")
-                        }
-                    }
-                    "pop" => {
-                        let item = live_stack.pop().expect("INTERP: error no argument to pop");
-                        if item.id == TokId::ARRAY {
-                            let Value::ARR(mut arr) = item.rep else {
-                                panic!("INTERP: expected Array but got this {}", item.rep);
-                            };
-                            if let Some(popped) = arr.pop() {
-                                live_stack.push(Object {
-                                    id: TokId::ARRAY,
-                                    rep: Value::ARR(arr),
-                                });
-                                live_stack.push(popped)
-                            }
-                        }
-                    }
-                    "fetch" => {
-                        let second = live_stack.pop().expect("INTERP: error no argument to fetch");
-                        let first = live_stack.pop().expect("INTERP: error no argument to fetch");
-                        if first.id == TokId::ARRAY {
-                            match second.id {
-                                TokId::INT => {
-                                    let Value::ARR(first_arr) = first.rep else {
-                                        panic!("INTERP: expected Array but got this {}", first.rep);
-                                    };
-                                    let mut ind32 = cast2int(cast2string(&second.rep));
-                                    if ind32 < 0 {
-                                        ind32 = first_arr.len() as i32 + ind32;
-                                    }
-                                    live_stack.push(
-                                        first_arr.get(ind32 as usize).expect("INTERP: array out of bounds").clone()
-                                    )
-                                }
-                                _ => panic!("INTERP: {} can't be indexed by {}", second.rep, first.rep)
-                            }
-                        } else if second.id == TokId::ARRAY {
-                            match first.id {
-                                TokId::INT => {
-                                    let Value::ARR(second_arr) = second.rep else {
-                                        panic!("INTERP: expected Array but got this {}", second.rep);
-                                    };
-                                    let mut ind32 = cast2int(cast2string(&first.rep));
-                                    if ind32 < 0 {
-                                        ind32 = second_arr.len() as i32 + ind32;
-                                    }
-                                    live_stack.push(
-                                        second_arr.get(ind32 as usize).expect("INTERP: array out of bounds").clone()
-                                    )
-                                }
-                                _ => panic!("INTERP: {} can't be indexed by {}", first.rep, second.rep)
-                            }
-                        } else {
-                            panic!("INTERP: no Array provided for fetch")
-                        }
-                    }
-                    "len" => {
-                        let item = live_stack.last().expect("INTERP: error no argument to get length of");
-                        match item.id {
-                            TokId::ARRAY => {
-                                let Value::ARR(arr) = &item.rep else {
-                                    panic!("INTERP: expected Array but got this {}", item.rep);
-                                };
-                                live_stack.push(Object {
-                                    id: TokId::INT,
-                                    rep: Value::STR(arr.len().to_string()),
-                                })
-                            }
-                            _ => live_stack.push(Object {
-                                id: TokId::INT,
-                                rep: Value::STR("0".to_string()),
-                            })
-                        }
-                    }
-                    def => {
-                        // variable casting or function call
-                        if block_level > -1 {
-                            // INSIDE A BLOCK
-                            for i in (0..block_level + 1).rev() {
-                                if vector_heap[i as usize].contains_key(def) {
-                                    let Some(value) = vector_heap[i as usize].get(def) else {
-                                        panic!("INTERP: {} value does not exist in local block's heap", def)
-                                    };
-                                    live_stack.push(value.clone());
-                                    continue 'main;
-                                }
-                            }
-                        }
+            let map = cast2map(map.rep)?;
+            let value = map.get(&object2key(&key)?).ok_or_else(|| NumenError::new(
+                ErrorKind::UNDEFINED,
+                format!("INTERP: the map has no key {}", key.rep),
+            ))?;
+            live_stack.push(value.clone());
+        }
+        "set" => {
+            let value = live_stack.pop().ok_or_else(|| underflow("INTERP: error no value to set"))?;
+            let key = live_stack.pop().ok_or_else(|| underflow("INTERP: error no key to set"))?;
+            let map = live_stack.pop().ok_or_else(|| underflow("INTERP: error no map to set in"))?;
+            if let Value::STRUCT(def, mut values) = map.rep {
+                values[field_index(&def, &key)?] = value;
+                live_stack.push(Object { id: TokId::INSTANCE, rep: Value::STRUCT(def, values), pos: pos.clone() });
+                return Ok(true);
+            }
+            let mut map = cast2map(map.rep)?;
+            map.insert(object2key(&key)?, value);
+            live_stack.push(Object { id: TokId::MAP, rep: Value::MAP(map), pos: pos.clone() });
+        }
+        "new" => {
+            // the fields are on the stack in order, the last one on top
+            let typ = live_stack.pop().ok_or_else(|| underflow("INTERP: error no struct to make"))?;
+            let Value::DEF(def) = typ.rep else {
+                return Err(mismatch(format!("INTERP: {} is not a struct", typ.rep)));
+            };
+            let start = live_stack.len().checked_sub(def.fields.len()).ok_or_else(|| underflow(format!(
+                "INTERP: {} needs {} fields but got {}",
+                def.name,
+                def.fields.len(),
+                live_stack.len(),
+            )))?;
+            let values = live_stack.split_off(start);
+            live_stack.push(Object { id: TokId::INSTANCE, rep: Value::STRUCT(def, values), pos: pos.clone() });
+        }
+        "has" => {
+            let key = live_stack.pop().ok_or_else(|| underflow("INTERP: error no key to look for"))?;
+            let map = live_stack.pop().ok_or_else(|| underflow("INTERP: error no map to look in"))?;
+            let map = cast2map(map.rep)?;
+            live_stack.push(boolean(map.contains_key(&object2key(&key)?), pos));
+        }
+        "remove" => {
+            let key = live_stack.pop().ok_or_else(|| underflow("INTERP: error no key to remove"))?;
+            let map = live_stack.pop().ok_or_else(|| underflow("INTERP: error no map to remove from"))?;
+            let mut map = cast2map(map.rep)?;
+            map.remove(&object2key(&key)?);
+            live_stack.push(Object { id: TokId::MAP, rep: Value::MAP(map), pos: pos.clone() });
+        }
+        "keys" => {
+            let map = live_stack.pop().ok_or_else(|| underflow("INTERP: error no map to get the keys of"))?;
+            let keys = cast2map(map.rep)?.keys().map(|key| key2object(key, pos)).collect();
+            live_stack.push(Object { id: TokId::ARRAY, rep: Value::ARR(keys), pos: pos.clone() });
+        }
+        "values" => {
+            let map = live_stack.pop().ok_or_else(|| underflow("INTERP: error no map to get the values of"))?;
+            let values = cast2map(map.rep)?.into_values().collect();
+            live_stack.push(Object { id: TokId::ARRAY, rep: Value::ARR(values), pos: pos.clone() });
+        }
+        _ => return math(name, live_stack, pos),
+    }
+    Ok(true)
+}
 
-                        if live_heap.contains_key(def) {
-                            // LOCAL VARIABLE
-                            let Some(value) = live_heap.get(def) else {
-                                panic!("INTERP: {} value does not exist in local heap", def)
-                            };
-                            live_stack.push(value.clone())
-                        } else if global_heap.contains_key(def) {
-                            // GLOBAL VARIABLE
-                            let Some(value) = global_heap.get(def) else {
-                                panic!("INTERP: {} value does not exist in local heap", def)
-                            };
-                            live_stack.push(value.clone())
-                        } else if function_map.contains_key(def) {
-                            //FUNCTION CALL
-                            interpret_func(
-                                function_map,
-                                def.to_string(),
-                                global_heap,
-                                Some(&mut live_stack),
-                                None,
-                                None,
-                            );
-                        }
-                    }
-                }
+// the math words, an int stays an int and a float on either side makes it a float like with + and -
+fn math(name: &str, live_stack: &mut Vec<Object>, pos: &Pos) -> Result<bool, NumenError> {
+    let (id, rep) = match name {
+        "abs" => match pop_number(live_stack, name)?.rep {
+            Value::INT(i) => (TokId::INT, Value::INT(i.checked_abs().ok_or_else(|| NumenError::new(
+                ErrorKind::ARITHMETIC,
+                format!("INTERP: abs of {} overflows an int, use bigint", i),
+            ))?)),
+            Value::BIG(big) if big < BigInt::from(0) => (TokId::BIGINT, Value::BIG(-&big)),
+            Value::FLOAT(f) => (TokId::FLOAT, Value::FLOAT(f.abs())),
+            rep => (TokId::BIGINT, rep),
+        },
+        // -1, 0 or 1, of the type of the number
+        "sign" => match pop_number(live_stack, name)?.rep {
+            Value::INT(i) => (TokId::INT, Value::INT(i.signum())),
+            Value::BIG(big) => (TokId::BIGINT, Value::BIG(BigInt::from(big.cmp(&BigInt::from(0)) as i64))),
+            Value::FLOAT(f) if f == 0.0 => (TokId::FLOAT, Value::FLOAT(f)),
+            rep => (TokId::FLOAT, Value::FLOAT(cast2f64(&rep).unwrap_or(f64::NAN).signum())),
+        },
+        "min" | "max" => {
+            let second = pop_number(live_stack, name)?;
+            let first = pop_number(live_stack, name)?;
+            let result = extreme(name, first.rep, second.rep, pos)?;
+            (result.id, result.rep)
+        }
+        // x lo hi clamp, x but no smaller than lo and no bigger than hi
+        "clamp" => {
+            let hi = pop_number(live_stack, name)?;
+            let lo = pop_number(live_stack, name)?;
+            let item = pop_number(live_stack, name)?;
+            if compare(&lo.rep, &hi.rep, "clamp")? == Some(Ordering::Greater) {
+                return Err(NumenError::new(
+                    ErrorKind::ARITHMETIC,
+                    format!("INTERP: clamp needs a lower bound {} that is not bigger than the upper bound {}", lo.rep, hi.rep),
+                ));
             }
-            TokId::FUNCTION => {
-                panic!("INTERP: can't declare functions inside a functions") // Not implemented
+            let result = extreme("max", item.rep, lo.rep, pos)?;
+            let result = extreme("min", result.rep, hi.rep, pos)?;
+            (result.id, result.rep)
+        }
+        // ints and bigints are whole already, round goes away from zero at .5
+        "floor" | "ceil" | "round" | "trunc" => {
+            let item = pop_number(live_stack, name)?;
+            let Value::FLOAT(f) = item.rep else {
+                live_stack.push(item);
+                return Ok(true);
+            };
+            let result = match name {
+                "floor" => f.floor(),
+                "ceil" => f.ceil(),
+                "round" => f.round(),
+                _ => f.trunc(),
+            };
+            (TokId::FLOAT, Value::FLOAT(result))
+        }
+        // ints and bigints become floats, so the root of 8 isn't cut to 2
+        "sqrt" | "sin" | "cos" | "tan" | "exp" | "ln" | "log10" => {
+            let x = pop_f64(live_stack, name)?;
+            let result = match name {
+                "sqrt" => x.sqrt(),
+                "sin" => x.sin(),
+                "cos" => x.cos(),
+                "tan" => x.tan(),
+                "exp" => x.exp(),
+                "ln" => x.ln(),
+                _ => x.log10(),
+            };
+            (TokId::FLOAT, Value::FLOAT(result))
+        }
+        // y x atan2, the angle of the point x y
+        "atan2" => {
+            let x = pop_f64(live_stack, name)?;
+            let y = pop_f64(live_stack, name)?;
+            (TokId::FLOAT, Value::FLOAT(y.atan2(x)))
+        }
+        _ => return Ok(false),
+    };
+    live_stack.push(Object { id, rep, pos: pos.clone() });
+    Ok(true)
+}
+
+// the smaller or bigger of two numbers
+fn extreme(name: &str, first: Value, second: Value, pos: &Pos) -> Result<Object, NumenError> {
+    if name == "min" {
+        arithmetic(first, second, "compared", |a, b| Some(a.min(b)), |a, b| Some(a.min(b).clone()), |a, b| Some(a.min(b)), pos)
+    } else {
+        arithmetic(first, second, "compared", |a, b| Some(a.max(b)), |a, b| Some(a.max(b).clone()), |a, b| Some(a.max(b)), pos)
+    }
+}
+
+fn pop_number(live_stack: &mut Vec<Object>, verb: &str) -> Result<Object, NumenError> {
+    let item = live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", verb)))?;
+    match item.id {
+        TokId::INT | TokId::BIGINT | TokId::FLOAT => Ok(item),
+        _ => Err(mismatch(format!("INTERP: {} needs a number but got {}", verb, item.rep))),
+    }
+}
+
+fn pop_f64(live_stack: &mut Vec<Object>, verb: &str) -> Result<f64, NumenError> {
+    let item = pop_number(live_stack, verb)?;
+    cast2f64(&item.rep).ok_or_else(|| mismatch(format!("INTERP: {} needs a number but got {}", verb, item.rep)))
+}
+
+// pops an int for the bitwise words
+fn pop_int(live_stack: &mut Vec<Object>, verb: &str) -> Result<i64, NumenError> {
+    let item = live_stack.pop().ok_or_else(|| underflow(format!("INTERP: error no argument to {}", verb)))?;
+    match item.rep {
+        Value::INT(int) if item.id == TokId::INT => Ok(int),
+        rep => Err(mismatch(format!("INTERP: {} needs ints but got {}", verb, rep))),
+    }
+}
+
+fn underflow(message: impl Into<String>) -> NumenError {
+    NumenError::new(ErrorKind::STACKUNDERFLOW, message)
+}
+
+fn mismatch(message: impl Into<String>) -> NumenError {
+    NumenError::new(ErrorKind::TYPEMISMATCH, message)
+}
+
+fn unbalanced(message: impl Into<String>) -> NumenError {
+    NumenError::new(ErrorKind::UNBALANCED, message)
+}
+
+// arithmetic on two numbers, an int stays an int unless the other one is a bigint
+// or a float, ints that leave 64 bits are an error instead of wrapping around
+fn arithmetic(
+    first: Value,
+    second: Value,
+    verb: &str,
+    int_op: fn(i64, i64) -> Option<i64>,
+    big_op: fn(&BigInt, &BigInt) -> Option<BigInt>,
+    float_op: fn(f64, f64) -> Option<f64>,
+    pos: &Pos,
+) -> Result<Object, NumenError> {
+    let (id, rep) = match (first, second) {
+        (Value::INT(a), Value::INT(b)) => {
+            let result = int_op(a, b).ok_or_else(|| match b {
+                0 => division_by_zero(),
+                _ => NumenError::new(
+                    ErrorKind::ARITHMETIC,
+                    format!("INTERP: {} and {} can't be {} without overflowing an int, use bigint", a, b, verb),
+                ),
+            })?;
+            (TokId::INT, Value::INT(result))
+        }
+        (first, second) => {
+            if let (Some(a), Some(b)) = (cast2big(&first), cast2big(&second)) {
+                (TokId::BIGINT, Value::BIG(big_op(&a, &b).ok_or_else(division_by_zero)?))
+            } else if let (Some(a), Some(b)) = (cast2f64(&first), cast2f64(&second)) {
+                (TokId::FLOAT, Value::FLOAT(float_op(a, b).ok_or_else(division_by_zero)?))
+            } else {
+                return Err(mismatch(format!("INTERP: {} and {} can't be {}", first, second, verb)))
             }
+        }
+    };
+    Ok(Object { id, rep, pos: pos.clone() })
+}
 
-            _ => {
-                live_stack.push(tok.clone());
+// / and quot truncate, div floors, % and rem have the sign of the dividend and
+// mod the one of the divisor, / divides floats without rounding
+fn divide(verb: &str, first: Value, second: Value, pos: &Pos) -> Result<Object, NumenError> {
+    match verb {
+        "/" => arithmetic(
+            first, second, "divided", i64::checked_div,
+            |a, b| a.div_rem(b).map(|(quotient, _)| quotient), |a, b| (b != 0.0).then(|| a / b), pos,
+        ),
+        "quot" => arithmetic(
+            first, second, "divided", i64::checked_div,
+            |a, b| a.div_rem(b).map(|(quotient, _)| quotient), |a, b| (b != 0.0).then(|| (a / b).trunc()), pos,
+        ),
+        "div" => arithmetic(
+            first, second, "divided", floor_div,
+            |a, b| a.div_mod_floor(b).map(|(quotient, _)| quotient), |a, b| (b != 0.0).then(|| (a / b).floor()), pos,
+        ),
+        "mod" => arithmetic(
+            first, second, "used to mod", floor_mod,
+            |a, b| a.div_mod_floor(b).map(|(_, rest)| rest), |a, b| (b != 0.0).then(|| floor_rest(a % b, b)), pos,
+        ),
+        _ => arithmetic(
+            first, second, "used to mod", |a, b| (b != 0).then(|| a.wrapping_rem(b)),
+            |a, b| a.div_rem(b).map(|(_, rest)| rest), |a, b| (b != 0.0).then(|| a % b), pos,
+        ),
+    }
+}
+
+fn floor_div(a: i64, b: i64) -> Option<i64> {
+    let quotient = a.checked_div(b)?;
+    Some(if a % b != 0 && (a < 0) != (b < 0) { quotient - 1 } else { quotient })
+}
+
+// i64::MIN % -1 is 0, there is nothing to overflow
+fn floor_mod(a: i64, b: i64) -> Option<i64> {
+    let rest = (b != 0).then(|| a.wrapping_rem(b))?;
+    Some(if rest != 0 && (rest < 0) != (b < 0) { rest + b } else { rest })
+}
+
+fn floor_rest(rest: f64, b: f64) -> f64 {
+    if rest != 0.0 && (rest < 0.0) != (b < 0.0) {
+        rest + b
+    } else {
+        rest
+    }
+}
+
+// an int or bigint raised to an int stays one, so its exponent can't be negative,
+// a float on either side makes it a float
+fn power(base: Value, exponent: Value, pos: &Pos) -> Result<Object, NumenError> {
+    let (id, rep) = match (cast2big(&base), cast2big(&exponent)) {
+        (Some(_), Some(e)) if e < BigInt::from(0) => {
+            return Err(NumenError::new(
+                ErrorKind::ARITHMETIC,
+                format!("INTERP: {} can't be raised to the negative power {}, use a float", base, e),
+            ));
+        }
+        (Some(_), Some(e)) if e > BigInt::from(u32::MAX as i64) => {
+            return Err(NumenError::new(ErrorKind::ARITHMETIC, format!("INTERP: the exponent {} is too large", e)));
+        }
+        // 0, 1 and -1 stay that small whatever the exponent
+        (Some(b), Some(e)) if b.bits() > 1 && b.bits().saturating_mul(e.to_f64() as u64) > POWER_BITS => {
+            return Err(NumenError::new(
+                ErrorKind::ARITHMETIC,
+                format!("INTERP: {} raised to {} has more than {} bits", b, e, POWER_BITS),
+            ));
+        }
+        (Some(b), Some(e)) => {
+            // it fits into a u32, so the float holds it exactly
+            let e = e.to_f64() as u32;
+            match (&base, &exponent) {
+                (Value::INT(b), Value::INT(_)) => {
+                    let result = b.checked_pow(e).ok_or_else(|| NumenError::new(
+                        ErrorKind::ARITHMETIC,
+                        format!("INTERP: {} can't be raised to {} without overflowing an int, use bigint", b, e),
+                    ))?;
+                    (TokId::INT, Value::INT(result))
+                }
+                _ => (TokId::BIGINT, Value::BIG(b.pow(e))),
+            }
+        }
+        _ => match (cast2f64(&base), cast2f64(&exponent)) {
+            (Some(b), Some(e)) => (TokId::FLOAT, Value::FLOAT(b.powf(e))),
+            _ => return Err(mismatch(format!("INTERP: {} and {} can't be raised", base, exponent))),
+        },
+    };
+    Ok(Object { id, rep, pos: pos.clone() })
+}
+
+fn division_by_zero() -> NumenError {
+    NumenError::new(ErrorKind::ARITHMETIC, "INTERP: division by zero")
+}
+
+// orders two numbers, None when one of them is nan
+fn compare(first: &Value, second: &Value, verb: &str) -> Result<Option<Ordering>, NumenError> {
+    if let (Value::INT(a), Value::INT(b)) = (first, second) {
+        Ok(Some(a.cmp(b)))
+    } else if let (Some(a), Some(b)) = (cast2big(first), cast2big(second)) {
+        Ok(Some(a.cmp(&b)))
+    } else if let (Some(a), Some(b)) = (cast2f64(first), cast2f64(second)) {
+        Ok(a.partial_cmp(&b))
+    } else {
+        Err(mismatch(format!("INTERP: {} and {} can't be compared for {}", first, second, verb)))
+    }
+}
+
+// ints and bigints as a bigint
+fn cast2big(val: &Value) -> Option<BigInt> {
+    match val {
+        Value::INT(i) => Some(BigInt::from(*i)),
+        Value::BIG(big) => Some(big.clone()),
+        _ => None,
+    }
+}
+
+// any number as a float
+fn cast2f64(val: &Value) -> Option<f64> {
+    match val {
+        Value::INT(i) => Some(*i as f64),
+        Value::BIG(big) => Some(big.to_f64()),
+        Value::FLOAT(f) => Some(*f),
+        _ => None,
+    }
+}
+
+// typed parameters only take values of their type
+fn typecheck(param: &Object, value: &Object) -> Result<(), NumenError> {
+    match param.id {
+        TokId::TINT | TokId::TBIGINT | TokId::TFLOAT | TokId::TSTRING | TokId::TBOOL | TokId::TARRAY | TokId::TMAP
+        | TokId::TQUOTE | TokId::TFUNCREF | TokId::TSTRUCT => {
+            if is_type(value, param) {
+                Ok(())
+            } else {
+                Err(mismatch(PARAM_MISMATCH))
             }
         }
+        _ => Err(NumenError::new(ErrorKind::UNDEFINED, format!("INTERP: {} is not a name of a type", param.id))),
     }
-    return live_stack;
 }
 
-fn cast2int(data: &String) -> i32 {
-    data.parse::<i32>().unwrap_or_else(|_|
-        panic!("INTERP: can't parse {} to int", data))
+fn is_type(value: &Object, typ: &Object) -> bool {
+    if let (Value::STRUCT(def, _), Value::DEF(typ)) = (&value.rep, &typ.rep) {
+        return def.name == typ.name;
+    }
+    matches!(
+        (value.id, typ.id),
+        (TokId::INT, TokId::TINT)
+            | (TokId::BIGINT, TokId::TBIGINT)
+            | (TokId::FLOAT, TokId::TFLOAT)
+            | (TokId::BOOLEAN, TokId::TBOOL)
+            | (TokId::STRING, TokId::TSTRING)
+            | (TokId::ARRAY, TokId::TARRAY)
+            | (TokId::MAP, TokId::TMAP)
+            | (TokId::QUOTE, TokId::TQUOTE)
+            | (TokId::FUNCREF, TokId::TFUNCREF)
+    )
 }
 
-fn cast2float(data: &String) -> f64 {
-    data.parse::<f64>()
-        .expect("INTERP: can't parse value to int")
+fn boolean(value: bool, pos: &Pos) -> Object {
+    Object {
+        id: TokId::BOOLEAN,
+        rep: Value::BOOL(value),
+        pos: pos.clone(),
+    }
 }
 
+// the logic words only take booleans, anything else is a mistake
+fn logical(obj: &Object, verb: &str) -> Result<bool, NumenError> {
+    match (obj.id, &obj.rep) {
+        (TokId::BOOLEAN, Value::BOOL(b)) => Ok(*b),
+        _ => Err(mismatch(format!("INTERP: {} needs booleans but got {}", verb, obj.rep))),
+    }
+}
 
-fn cast2string(val: &Value) -> &String {
-    match &val {
-        Value::STR(s) => s,
-        Value::ARR(_) => panic!("INTERP: can't cast array as string")
+fn cast2int(val: &Value) -> Result<i64, NumenError> {
+    match val {
+        Value::INT(i) => Ok(*i),
+        _ => Err(mismatch(format!("INTERP: {} is not an int", val))),
     }
 }
 
-fn cast2str(val: Value) -> String {
+fn cast2bool(val: &Value) -> Result<bool, NumenError> {
     match val {
-        Value::STR(s) => s,
-        Value::ARR(_) => panic!("INTERP: can't cast array as string")
+        Value::BOOL(b) => Ok(*b),
+        _ => Err(mismatch(format!("INTERP: {} is not a boolean", val))),
+    }
+}
+
+// names of variables, functions and parameters
+fn cast2name(val: &Value) -> Result<&str, NumenError> {
+    match val {
+        Value::STR(s) => Ok(s),
+        _ => Err(mismatch(format!("INTERP: {} is not a name", val))),
+    }
+}
+
+// the pairs of a map literal, keys come before their values
+fn collect_map(items: Vec<Object>, pos: &Pos) -> Result<Object, NumenError> {
+    if !items.len().is_multiple_of(2) {
+        return Err(NumenError::new(
+            ErrorKind::INVALIDVALUE,
+            "INTERP: the map literal has an odd number of items, every key needs a value",
+        ));
+    }
+    let mut map: BTreeMap<Key, Object> = BTreeMap::new();
+    let mut items = items.into_iter();
+    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+        map.insert(object2key(&key)?, value);
+    }
+    Ok(Object { id: TokId::MAP, rep: Value::MAP(map), pos: pos.clone() })
+}
+
+// where the field named by key is kept in the instances of a struct
+fn field_index(def: &StructDef, key: &Object) -> Result<usize, NumenError> {
+    def.fields
+        .iter()
+        .position(|field| key.id == TokId::STRING && key.rep == Value::STR(field.clone()))
+        .ok_or_else(|| NumenError::new(
+            ErrorKind::UNDEFINED,
+            format!("INTERP: the struct {} has no field {}", def.name, key.rep),
+        ))
+}
+
+fn cast2map(val: Value) -> Result<BTreeMap<Key, Object>, NumenError> {
+    match val {
+        Value::MAP(map) => Ok(map),
+        _ => Err(mismatch(format!("INTERP: {} is not a map", val))),
+    }
+}
+
+// strings, ints, bigints and bools can be keys
+fn object2key(obj: &Object) -> Result<Key, NumenError> {
+    match &obj.rep {
+        Value::STR(s) if obj.id == TokId::STRING => Ok(Key::STR(s.clone())),
+        Value::INT(i) => Ok(Key::INT(*i)),
+        Value::BIG(big) => Ok(big.to_i64().map_or_else(|| Key::BIG(big.clone()), Key::INT)),
+        Value::BOOL(b) => Ok(Key::BOOL(*b)),
+        _ => Err(mismatch(format!("INTERP: {} can't be a map key", obj.rep))),
+    }
+}
+
+fn key2object(key: &Key, pos: &Pos) -> Object {
+    let (id, rep) = match key {
+        Key::BOOL(b) => (TokId::BOOLEAN, Value::BOOL(*b)),
+        Key::INT(i) => (TokId::INT, Value::INT(*i)),
+        Key::BIG(big) => (TokId::BIGINT, Value::BIG(big.clone())),
+        Key::STR(s) => (TokId::STRING, Value::STR(s.clone())),
+    };
+    Object { id, rep, pos: pos.clone() }
+}
+
+// strings are quoted inside arrays and maps
+fn item2string(item: &Object) -> String {
+    match &item.rep {
+        Value::STR(s) if item.id == TokId::STRING => format!("\"{}\"", s),
+        Value::STR(s) if item.id == TokId::FUNCREF => format!("&{}", s),
+        rep => rep.to_string(),
     }
 }
 
 pub fn array2string(arr: Vec<Object>) -> String {
     let mut result: String = String::from("[ ");
     for item in arr {
-        match item.rep {
-            Value::STR(s) => {
-                if item.id == TokId::STRING {
-                    result += ("\"".to_string() + s.as_str() + "\" ").as_str()
-                } else {
-                    result += (s + " ").as_str()
-                }
+        result += &format!("{} ", item2string(&item));
+    }
+    result += "]";
+    result
+}
+
+pub fn struct2string(def: &StructDef, values: &[Object]) -> String {
+    let mut result: String = format!("{} {{ ", def.name);
+    for (field, value) in def.fields.iter().zip(values) {
+        result += &format!("{} {} ", field, item2string(value));
+    }
+    result += "}";
+    result
+}
+
+pub fn quote2string(quote: &Quotation) -> String {
+    let mut result: String = String::from("( ");
+    for item in quote.source.iter().filter(|item| item.id != TokId::LINEBREAK) {
+        result += &format!("{} ", item2string(item));
+    }
+    result += ")";
+    result
+}
+
+pub fn map2string(map: &BTreeMap<Key, Object>) -> String {
+    let mut result: String = String::from("{ ");
+    for (key, value) in map {
+        result += &format!("{} {} ", item2string(&key2object(key, &value.pos)), item2string(value));
+    }
+    result += "}";
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::head::tests::number;
+
+    // runs an operator or builtin on the values and returns what it leaves on top
+    fn run(word: &str, values: Vec<Value>) -> Result<Value, NumenError> {
+        let mut live_stack: Vec<Object> = values.into_iter().map(number).collect();
+        let pos = Pos::default();
+        match word {
+            "/" => operator(TokId::DIVIDE, &mut live_stack, &pos)?,
+            "%" => operator(TokId::MOD, &mut live_stack, &pos)?,
+            "**" => operator(TokId::POWER, &mut live_stack, &pos)?,
+            _ => assert!(builtin(word, &mut live_stack, &pos)?, "{} is no builtin", word),
+        }
+        Ok(live_stack.pop().unwrap().rep)
+    }
+
+    // runs a program, its asserts tell whether it works
+    fn program(source: &str) -> Result<(), NumenError> {
+        let fmap = crate::parser::parse_file(crate::lexer::lexer_file(source, "test.nm")?)?;
+        interpret(compile(&fmap)?)
+    }
+
+    fn ints(word: &str, a: i64, b: i64) -> Option<i64> {
+        match run(word, vec![Value::INT(a), Value::INT(b)]) {
+            Ok(Value::INT(i)) => Some(i),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn divides_ints_with_every_sign() {
+        // a b, then / quot div % rem mod
+        let cases = [
+            (7, 2, [3, 3, 3, 1, 1, 1]),
+            (-7, 2, [-3, -3, -4, -1, -1, 1]),
+            (7, -2, [-3, -3, -4, 1, 1, -1]),
+            (-7, -2, [3, 3, 3, -1, -1, -1]),
+            (6, -3, [-2, -2, -2, 0, 0, 0]),
+        ];
+        for (a, b, expected) in cases {
+            for (word, result) in ["/", "quot", "div", "%", "rem", "mod"].iter().zip(expected) {
+                assert_eq!(ints(word, a, b), Some(result), "{} {} {}", a, b, word);
             }
-            Value::ARR(a) => result += (array2string(a) + " ").as_str(),
         }
     }
-    result += "]";
-    return result;
-}
\ No newline at end of file
+
+    #[test]
+    fn stops_at_division_by_zero_and_overflow() {
+        for word in ["/", "quot", "div", "%", "rem", "mod"] {
+            assert!(ints(word, 1, 0).is_none(), "{}", word);
+            assert!(run(word, vec![Value::FLOAT(1.0), Value::FLOAT(0.0)]).is_err(), "{}", word);
+            assert!(run(word, vec![Value::BIG(BigInt::from(1)), Value::INT(0)]).is_err(), "{}", word);
+        }
+        assert_eq!(ints("/", i64::MIN, -1), None);
+        assert_eq!(ints("div", i64::MIN, -1), None);
+        assert_eq!(ints("mod", i64::MIN, -1), Some(0));
+        assert_eq!(ints("rem", i64::MIN, -1), Some(0));
+    }
+
+    #[test]
+    fn divides_floats_and_bigints_with_every_sign() {
+        let floats = |word, a, b| run(word, vec![Value::FLOAT(a), Value::FLOAT(b)]).unwrap();
+        assert_eq!(floats("/", -7.0, 2.0), Value::FLOAT(-3.5));
+        assert_eq!(floats("quot", -7.0, 2.0), Value::FLOAT(-3.0));
+        assert_eq!(floats("div", -7.0, 2.0), Value::FLOAT(-4.0));
+        assert_eq!(floats("rem", -7.5, 2.0), Value::FLOAT(-1.5));
+        assert_eq!(floats("mod", -7.5, 2.0), Value::FLOAT(0.5));
+        assert_eq!(floats("mod", 7.5, -2.0), Value::FLOAT(-0.5));
+        let big = |word| run(word, vec![Value::BIG(BigInt::from(-7)), Value::INT(2)]).unwrap();
+        assert_eq!(big("quot"), Value::BIG(BigInt::from(-3)));
+        assert_eq!(big("div"), Value::BIG(BigInt::from(-4)));
+        assert_eq!(big("rem"), Value::BIG(BigInt::from(-1)));
+        assert_eq!(big("mod"), Value::BIG(BigInt::from(1)));
+    }
+
+    #[test]
+    fn raises_to_powers() {
+        assert_eq!(ints("**", 2, 10), Some(1024));
+        assert_eq!(ints("pow", -3, 3), Some(-27));
+        assert_eq!(ints("**", 5, 0), Some(1));
+        assert_eq!(ints("**", 2, -1), None);
+        assert_eq!(ints("**", 2, 63), None);
+        assert_eq!(
+            run("**", vec![Value::BIG(BigInt::from(2)), Value::INT(64)]).unwrap(),
+            Value::BIG(BigInt::parse_radix("18446744073709551616", 10).unwrap()),
+        );
+        assert_eq!(run("**", vec![Value::INT(4), Value::FLOAT(0.5)]).unwrap(), Value::FLOAT(2.0));
+        assert_eq!(run("**", vec![Value::FLOAT(2.0), Value::INT(-1)]).unwrap(), Value::FLOAT(0.5));
+        // too large to compute in time, but 1 stays 1
+        assert!(run("**", vec![Value::BIG(BigInt::from(2)), Value::INT(4000000000)]).is_err());
+        assert_eq!(run("**", vec![Value::BIG(BigInt::from(-1)), Value::INT(4000000001)]).unwrap(), Value::BIG(BigInt::from(-1)));
+    }
+
+    fn float(word: &str, x: f64) -> Value {
+        run(word, vec![Value::FLOAT(x)]).unwrap()
+    }
+
+    #[test]
+    fn rounds_floats_with_every_sign() {
+        // x, then floor ceil round trunc
+        let cases = [
+            (2.5, [2.0, 3.0, 3.0, 2.0]),
+            (-2.5, [-3.0, -2.0, -3.0, -2.0]),
+            (2.4, [2.0, 3.0, 2.0, 2.0]),
+            (-2.6, [-3.0, -2.0, -3.0, -2.0]),
+            (0.0, [0.0, 0.0, 0.0, 0.0]),
+        ];
+        for (x, expected) in cases {
+            for (word, result) in ["floor", "ceil", "round", "trunc"].iter().zip(expected) {
+                assert_eq!(float(word, x), Value::FLOAT(result), "{} {}", x, word);
+            }
+        }
+        assert_eq!(run("round", vec![Value::INT(-7)]).unwrap(), Value::INT(-7));
+        assert_eq!(run("floor", vec![Value::BIG(BigInt::from(-7))]).unwrap(), Value::BIG(BigInt::from(-7)));
+    }
+
+    #[test]
+    fn keeps_the_type_in_abs_sign_min_and_max() {
+        assert_eq!(run("abs", vec![Value::INT(-3)]).unwrap(), Value::INT(3));
+        assert!(run("abs", vec![Value::INT(i64::MIN)]).is_err());
+        assert_eq!(run("abs", vec![Value::BIG(BigInt::from(-3))]).unwrap(), Value::BIG(BigInt::from(3)));
+        assert_eq!(float("abs", -2.5), Value::FLOAT(2.5));
+        assert_eq!(run("sign", vec![Value::INT(-9)]).unwrap(), Value::INT(-1));
+        assert_eq!(run("sign", vec![Value::BIG(BigInt::from(0))]).unwrap(), Value::BIG(BigInt::from(0)));
+        assert_eq!(run("sign", vec![Value::BIG(BigInt::from(-7))]).unwrap(), Value::BIG(BigInt::from(-1)));
+        assert_eq!(float("sign", -0.5), Value::FLOAT(-1.0));
+        assert_eq!(float("sign", 0.0), Value::FLOAT(0.0));
+        assert_eq!(ints("min", -3, 2), Some(-3));
+        assert_eq!(ints("max", -3, 2), Some(2));
+        assert_eq!(run("max", vec![Value::INT(3), Value::FLOAT(2.5)]).unwrap(), Value::FLOAT(3.0));
+        assert!(run("min", vec![Value::INT(3), Value::BOOL(true)]).is_err());
+    }
+
+    #[test]
+    fn clamps_between_the_bounds() {
+        let clamp = |x, lo, hi| run("clamp", vec![Value::INT(x), Value::INT(lo), Value::INT(hi)]);
+        assert_eq!(clamp(15, 0, 10).unwrap(), Value::INT(10));
+        assert_eq!(clamp(-5, 0, 10).unwrap(), Value::INT(0));
+        assert_eq!(clamp(5, 0, 10).unwrap(), Value::INT(5));
+        assert_eq!(clamp(5, 5, 5).unwrap(), Value::INT(5));
+        assert!(clamp(5, 10, 0).is_err());
+    }
+
+    #[test]
+    fn gives_floats_for_roots_and_logarithms() {
+        assert_eq!(run("sqrt", vec![Value::INT(9)]).unwrap(), Value::FLOAT(3.0));
+        assert_eq!(run("sqrt", vec![Value::INT(8)]).unwrap(), Value::FLOAT(8f64.sqrt()));
+        assert!(matches!(run("sqrt", vec![Value::INT(-4)]).unwrap(), Value::FLOAT(f) if f.is_nan()));
+        assert_eq!(run("log10", vec![Value::BIG(BigInt::from(1000))]).unwrap(), Value::FLOAT(3.0));
+        assert_eq!(run("ln", vec![Value::INT(1)]).unwrap(), Value::FLOAT(0.0));
+        assert_eq!(run("atan2", vec![Value::INT(0), Value::INT(-1)]).unwrap(), Value::FLOAT(std::f64::consts::PI));
+        assert!(run("sin", vec![]).is_err());
+    }
+
+    #[test]
+    fn runs_quotations_with_the_variables_they_were_pushed_with() {
+        program("
+            fun apply_to q x as 100 = n x q call ret end
+            fun main as 10 = n ( n + ) = add 5 add apply_to 15 assert end
+        ").unwrap();
+        program("
+            fun counter as 0 = count ( count 1 + = count count ) ret end
+            fun main as counter = c c call drop c call 2 assert end
+        ").unwrap();
+        program("fun mk as 5 = v ( v ) ret end mk call 5 assert").unwrap();
+        program("fun f as true if 4 = b ( b 1 + ) ret end end f call 5 assert").unwrap();
+        program("( 7 = x ) call x 7 assert").unwrap();
+        program("fun f as 0 = s [ 1 2 3 ] ( s + = s ) each s 6 assert end f").unwrap();
+        assert!(program("fun f as ( z ) ret end fun g as 1 = z f call end g").is_err());
+    }
+
+    #[test]
+    fn suggests_functions_for_names_that_are_called_but_do_not_exist() {
+        let err = program("fun square x as x x * ret end 3 \"sqare\" exec").unwrap_err();
+        assert!(err.message.contains("sqare is no builtin, variable or function, did you mean square?"), "{}", err.message);
+        let err = program("fun square x as x x * ret end 3 &sqare call").unwrap_err();
+        assert!(err.message.contains("did you mean square?"), "{}", err.message);
+        program("fun square x as x x * ret end 3 \"square\" exec 9 assert").unwrap();
+    }
+
+    #[test]
+    fn returns_from_inside_blocks() {
+        program("fun f as 5 loop true if it 2 == if it ret end end end -1 ret end f 2 assert").unwrap();
+        program("fun f as 1 2 let a b as a b + ret end 0 ret end f 3 assert").unwrap();
+        program("fun f n as n loop it 3 == if [ it ] ret end end [ ] ret end 7 f 0 fetch 3 assert").unwrap();
+    }
+
+    #[test]
+    fn breaks_and_continues_from_inside_blocks() {
+        program("0 = s 10 loop it 5 == if break end s it + = s end s 10 assert").unwrap();
+        program("0 = s 5 loop it 2 % 0 == if continue end s it + = s end s 4 assert").unwrap();
+        program("0 = i 0 = s while i 5 < do i 1 + = i i 3 == if continue end s i + = s end s 12 assert").unwrap();
+        // what the left blocks had on their stacks is gone
+        program("42 3 loop [ 1 2 break ] end 42 assert").unwrap();
+        // only the innermost loop ends
+        program("0 = c 3 loop 3 loop break end c 1 + = c end c 3 assert").unwrap();
+    }
+}
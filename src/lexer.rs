@@ -1,67 +1,175 @@
+use crate::error::{ErrorKind, NumenError};
 use crate::head;
-use head::{Lexeme, TokId};
-
-const LEXMAP: [Lexeme<&'static str>; 31] = [
-    Lexeme { id: TokId::FUNCTION, rep: "fun" },
-    Lexeme { id: TokId::IMPORT, rep: "import" },
-    Lexeme { id: TokId::END, rep: "end" },
-    Lexeme { id: TokId::AS, rep: "as" },
-    Lexeme { id: TokId::RET, rep: "ret" },
-    Lexeme { id: TokId::WHILE, rep: "while" },
-    Lexeme { id: TokId::DO, rep: "do" },
-    Lexeme { id: TokId::IF, rep: "if" },
-    Lexeme { id: TokId::BLOCK, rep: "let" },
-    Lexeme { id: TokId::LOOP, rep: "loop" },
-    Lexeme { id: TokId::ELSE, rep: "else" },
-    Lexeme { id: TokId::PLUS, rep: "+" },
-    Lexeme { id: TokId::MINUS, rep: "-" },
-    Lexeme { id: TokId::MULTIPLY, rep: "*" },
-    Lexeme { id: TokId::DIVIDE, rep: "/" },
-    Lexeme { id: TokId::MOD, rep: "%" },
-    Lexeme { id: TokId::ASSIGNMENT, rep: "=" },
-    Lexeme { id: TokId::RETURNINGASSIGNMENT, rep: "=>" },
-    Lexeme { id: TokId::EQUALS, rep: "==" },
-    Lexeme { id: TokId::BIGGER, rep: ">" },
-    Lexeme { id: TokId::SMALLER, rep: "<" },
-    Lexeme { id: TokId::SMALLEREQUALS, rep: "<=" },
-    Lexeme { id: TokId::BIGGEREQUALS, rep: ">=" },
-    Lexeme { id: TokId::TINT, rep: "int" },
-    Lexeme { id: TokId::TFLOAT, rep: "float" },
-    Lexeme { id: TokId::TSTRING, rep: "str" },
-    Lexeme { id: TokId::TBOOL, rep: "bool" },
-    Lexeme { id: TokId::TARRAY, rep: "array" },
-    Lexeme { id: TokId::IS, rep: "is" },
-    Lexeme { id: TokId::ARRAYBEGIN, rep: "[" },
-    Lexeme { id: TokId::ARRAYEND, rep: "]" },
+use head::{Lexeme, Pos, TokId};
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+
+const LEXMAP: [(TokId, &str); 49] = [
+    (TokId::FUNCTION, "fun"),
+    (TokId::STRUCT, "struct"),
+    (TokId::IMPORT, "import"),
+    (TokId::END, "end"),
+    (TokId::AS, "as"),
+    (TokId::EFFECT, "--"),
+    (TokId::RET, "ret"),
+    (TokId::WHILE, "while"),
+    (TokId::DO, "do"),
+    (TokId::IF, "if"),
+    (TokId::BLOCK, "let"),
+    (TokId::LOOP, "loop"),
+    (TokId::BREAK, "break"),
+    (TokId::CONTINUE, "continue"),
+    (TokId::ELSE, "else"),
+    (TokId::PLUS, "+"),
+    (TokId::MINUS, "-"),
+    (TokId::MULTIPLY, "*"),
+    (TokId::DIVIDE, "/"),
+    (TokId::MOD, "%"),
+    (TokId::POWER, "**"),
+    (TokId::ASSIGNMENT, "="),
+    (TokId::RETURNINGASSIGNMENT, "=>"),
+    (TokId::EQUALS, "=="),
+    (TokId::BIGGER, ">"),
+    (TokId::SMALLER, "<"),
+    (TokId::SMALLEREQUALS, "<="),
+    (TokId::BIGGEREQUALS, ">="),
+    (TokId::NOTEQUALS, "!="),
+    (TokId::AND, "and"),
+    (TokId::OR, "or"),
+    (TokId::XOR, "xor"),
+    (TokId::NOT, "not"),
+    (TokId::TINT, "int"),
+    (TokId::TBIGINT, "bigint"),
+    (TokId::TFLOAT, "float"),
+    (TokId::TSTRING, "str"),
+    (TokId::TBOOL, "bool"),
+    (TokId::TARRAY, "array"),
+    (TokId::TMAP, "map"),
+    (TokId::TQUOTE, "quotation"),
+    (TokId::TFUNCREF, "function"),
+    (TokId::IS, "is"),
+    (TokId::ARRAYBEGIN, "["),
+    (TokId::ARRAYEND, "]"),
+    (TokId::MAPBEGIN, "{"),
+    (TokId::MAPEND, "}"),
+    (TokId::QUOTEBEGIN, "("),
+    (TokId::QUOTEEND, ")"),
 ];
 
 
 pub fn lexmap_contains_value(comp: &str) -> Option<TokId> {
-    for lex in LEXMAP {
-        if lex.rep == comp {
-            return Some(lex.id.clone());
+    for (id, rep) in LEXMAP {
+        if rep == comp {
+            return Some(id);
         }
     }
-    return None;
+    None
 }
 
 // converts the raw file string to a lexed vector (semi parsed)
-pub fn lexer_file(file: &String) -> Vec<Lexeme<String>> {
+pub fn lexer_file(file: &str, filename: &str) -> Result<Vec<Lexeme<String>>, NumenError> {
+    let filename: Rc<str> = Rc::from(filename);
     let mut result: Vec<Lexeme<String>> = Vec::new();
     let mut word: Vec<char> = Vec::new();
-    //let mut raw_string: Vec<char> = Vec::new();
+    // "" strings read escapes, '' strings are raw, tripled quotes can span lines
     let mut string_mode: Option<char> = None;
-    for char in file.chars() {
-        // in raw string mode
-        if string_mode.is_some() {
-            // end of raw string
-            if char == string_mode.unwrap() {
-                result.push(Lexeme {
-                    id: TokId::STRING,
-                    rep: word_to_string(&word).clone(),
-                });
+    let mut triple = false;
+    let mut path_mode = false;
+    // # runs to the end of the line, ## does too but keeps the text as a doc comment
+    let mut line_comment = false;
+    let mut doc_comment = false;
+    // depth of nested #[ ]# comments and where the outermost one starts
+    let mut block_comment = 0;
+    let mut block_start = (1, 1);
+    // position of the current char and of the first char of the word
+    let (mut line, mut col) = (1, 1);
+    let mut start = (1, 1);
+    let pos = |start: (usize, usize), len: usize| Pos {
+        file: filename.clone(),
+        line: start.0,
+        col: start.1,
+        len,
+    };
+    let lexeme = |id: TokId, word: &[char], start: (usize, usize), len: usize| Lexeme {
+        id,
+        rep: word_to_string(word),
+        pos: pos(start, len),
+    };
+    let mut chars = file.chars().peekable();
+    while let Some(char) = chars.next() {
+        let here = (line, col);
+        if char == '\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+        // block comments nest, so each #[ needs its own ]#
+        if block_comment > 0 {
+            if char == '#' && chars.next_if_eq(&'[').is_some() {
+                col += 1;
+                block_comment += 1;
+            } else if char == ']' && chars.next_if_eq(&'#').is_some() {
+                col += 1;
+                block_comment -= 1;
+            }
+            continue;
+        }
+        if line_comment || doc_comment {
+            if char != '\n' {
+                word.push(char);
+                continue;
+            }
+            if doc_comment {
+                let text: Vec<char> = word_to_string(&word).trim().chars().collect();
+                result.push(lexeme(TokId::DOC, &text, start, word.len() + 2));
+            }
+            word.clear();
+            line_comment = false;
+            doc_comment = false;
+            result.push(lexeme(TokId::LINEBREAK, &['\n'], here, 1));
+            continue;
+        }
+        // in import path mode, unquoted paths like std/math are read until whitespace
+        if path_mode {
+            if char.is_whitespace() {
+                result.push(lexeme(TokId::STRING, &word, start, word.len()));
+                word.clear();
+                path_mode = false;
+                if char == '\n' {
+                    result.push(lexeme(TokId::LINEBREAK, &['\n'], here, 1));
+                }
+            } else {
+                word.push(char);
+            }
+            continue;
+        }
+        // in string mode
+        if let Some(quote) = string_mode {
+            // end of string
+            if char == quote && (!triple || chars.clone().take(2).eq([quote, quote])) {
+                if triple {
+                    chars.nth(1);
+                    col += 2;
+                }
+                // a string over several lines only marks its opening quotes
+                let len = if line == start.0 { col - start.1 } else if triple { 3 } else { 1 };
+                result.push(lexeme(TokId::STRING, &word, start, len));
                 word.clear();
                 string_mode = None;
+            } else if char == '\n' && !triple {
+                return Err(NumenError::new(
+                    ErrorKind::SYNTAX,
+                    format!("LEXER: {} is missing at the end of the line, strings over several lines use {}", quote, quote.to_string().repeat(3)),
+                ).at(&pos(start, 1)));
+            } else if char == '\\' && quote == '"' {
+                let (escaped, len) = escape(&mut chars).ok_or_else(|| NumenError::new(
+                    ErrorKind::SYNTAX,
+                    "LEXER: unknown escape sequence",
+                ).at(&pos(here, 2)))?;
+                col += len;
+                word.push(escaped);
             } else {
                 // continue to push into word
                 word.push(char);
@@ -69,46 +177,74 @@ pub fn lexer_file(file: &String) -> Vec<Lexeme<String>> {
             continue; // must continue to avoid parsing
         }
 
-        if char == '[' {
-            if word.len() > 0 {
+        if char == '#' {
+            if let Some(id_of) = lexmap_contains_value(&word_to_string(&word)) {
+                result.push(lexeme(id_of, &word, start, word.len()));
+            } else if !word.is_empty() {
+                result.push(lexeme(TokId::UNKNOWN, &word, start, word.len()));
+            }
+            word.clear();
+            start = here;
+            if chars.next_if_eq(&'[').is_some() {
+                col += 1;
+                block_comment = 1;
+                block_start = here;
+            } else if chars.next_if_eq(&'#').is_some() {
+                col += 1;
+                doc_comment = true;
+            } else {
+                line_comment = true;
+            }
+            continue;
+        }
+
+        if word.is_empty()
+            && !char.is_whitespace()
+            && char != '"'
+            && char != '\''
+            && result.last().is_some_and(|lex| lex.id == TokId::IMPORT)
+        {
+            path_mode = true;
+            start = here;
+            word.push(char);
+            continue;
+        }
+
+        // brackets stand alone, so [1 and {"a" split up
+        if matches!(char, '[' | ']' | '{' | '}' | '(' | ')') {
+            if !word.is_empty() {
                 // this will only run when the word is not empty
-                result.push(Lexeme {
-                    id: TokId::UNKNOWN,
-                    rep: word_to_string(&word).clone(),
-                });
+                result.push(lexeme(word_id(&word), &word, start, word.len()));
                 word.clear();
             }
-            result.push(Lexeme {
-                id: TokId::ARRAYBEGIN,
-                rep: char.to_string(),
-            });
+            result.push(lexeme(word_id(&[char]), &[char], here, 1));
             continue;
         }
 
-        // if there is a raw string
+        // if there is a string
         if char == '"' || char == '\'' {
-            if word.len() > 0 {
+            if !word.is_empty() {
                 // this will only run when the word is not empty
-                result.push(Lexeme {
-                    id: TokId::UNKNOWN,
-                    rep: word_to_string(&word).clone(),
-                });
+                result.push(lexeme(word_id(&word), &word, start, word.len()));
                 word.clear();
             }
             string_mode = Some(char);
-            continue; // continue into raw string
+            triple = chars.clone().take(2).eq([char, char]);
+            if triple {
+                chars.nth(1);
+                col += 2;
+            }
+            start = here;
+            continue; // continue into string
         }
 
-        // word is something
-        if let Some(_id_of) = lexmap_contains_value(&word_to_string(&word)) {
+        // word is something, ! is not so Point.x! stays a word, but it starts !=
+        if lexmap_contains_value(&word_to_string(&word)).is_some() || word == ['!'] {
             let mut word_c = word.clone(); // created temporary for word + char
             word_c.push(char);
             // word + char is something
             if let Some(id_of) = lexmap_contains_value(&word_to_string(&word_c)) {
-                result.push(Lexeme {
-                    id: id_of,
-                    rep: word_to_string(&word_c).clone(),
-                });
+                result.push(lexeme(id_of, &word_c, start, word_c.len()));
                 word.clear();
                 word_c.clear();
                 continue; // continue to ignore vacant char
@@ -116,70 +252,158 @@ pub fn lexer_file(file: &String) -> Vec<Lexeme<String>> {
         }
 
         //char is something
-        if let Some(_) = lexmap_contains_value(&char.to_string()) {
+        if lexmap_contains_value(&char.to_string()).is_some() {
             // small token
-            if word.len() > 0 {
+            if !word.is_empty() {
                 // this will only run when the word is not empty
-                result.push(Lexeme {
-                    id: TokId::UNKNOWN,
-                    rep: word_to_string(&word).clone(),
-                });
+                result.push(lexeme(word_id(&word), &word, start, word.len()));
                 word.clear();
             }
+            start = here;
             word.push(char); // push into the word for next time
         } else {
             // char is not a token
             //char is a break
             if char == '\r' || char == '\t' || char.is_whitespace() {
-                if char == '\n' {
-                    result.push(Lexeme {
-                        id: TokId::LINEBREAK,
-                        rep: '\n'.to_string(),
-                    });
-                }
                 // word is something
                 if let Some(id_of) = lexmap_contains_value(&word_to_string(&word)) {
-                    result.push(Lexeme {
-                        id: id_of,
-                        rep: word_to_string(&word).clone(),
-                    });
+                    result.push(lexeme(id_of, &word, start, word.len()));
                     word.clear();
                 }
                 // word exists and is unknown
-                if word.len() > 0 {
+                if !word.is_empty() {
                     // this will only run when the word is something
-                    result.push(Lexeme {
-                        id: TokId::UNKNOWN,
-                        rep: word_to_string(&word).clone(),
-                    });
+                    result.push(lexeme(TokId::UNKNOWN, &word, start, word.len()));
                     word.clear();
                 }
+                // linebreak goes after the word it ends
+                if char == '\n' {
+                    result.push(lexeme(TokId::LINEBREAK, &['\n'], here, 1));
+                }
             } else {
                 // char is not a known thing, pushed to word
+                if word.is_empty() {
+                    start = here;
+                }
                 word.push(char);
             }
         }
     } // end of for
+    if block_comment > 0 {
+        return Err(NumenError::new(
+            ErrorKind::UNBALANCED,
+            "LEXER: ']#' is missing for the block comment",
+        ).at(&pos(block_start, 2)));
+    }
+    if let Some(quote) = string_mode {
+        let quotes = if triple { 3 } else { 1 };
+        return Err(NumenError::new(
+            ErrorKind::UNBALANCED,
+            format!("LEXER: {} is missing for the string", quote.to_string().repeat(quotes)),
+        ).at(&pos(start, quotes)));
+    }
+    // a comment on the last line
+    if line_comment {
+        word.clear();
+    }
+    if doc_comment {
+        let text: Vec<char> = word_to_string(&word).trim().chars().collect();
+        result.push(lexeme(TokId::DOC, &text, start, word.len() + 2));
+        word.clear();
+    }
     // if something is left
-    if word.len() > 0 {
+    if !word.is_empty() {
         // something important
-        if let Some(id_of) = lexmap_contains_value(&word_to_string(&word)) {
-            result.push(Lexeme {
-                id: id_of,
-                rep: word_to_string(&word).clone(),
-            });
+        if path_mode {
+            result.push(lexeme(TokId::STRING, &word, start, word.len()));
+        } else if let Some(id_of) = lexmap_contains_value(&word_to_string(&word)) {
+            result.push(lexeme(id_of, &word, start, word.len()));
         } else {
             // unknown
-            result.push(Lexeme {
-                id: TokId::UNKNOWN,
-                rep: word_to_string(&word).clone(),
-            });
+            result.push(lexeme(TokId::UNKNOWN, &word, start, word.len()));
         }
     }
     word.clear();
-    result
+    Ok(result)
+}
+
+// reads what follows a backslash, returns the char it stands for and how many
+// chars it took, None when it is not a known escape
+fn escape(chars: &mut Peekable<Chars>) -> Option<(char, usize)> {
+    let escaped = match chars.next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        '\'' => '\'',
+        'u' => {
+            // \u{1F600}
+            chars.next_if_eq(&'{')?;
+            let mut hex = String::new();
+            while let Some(digit) = chars.next_if(|c| c.is_ascii_hexdigit()) {
+                hex.push(digit);
+            }
+            chars.next_if_eq(&'}')?;
+            let code = u32::from_str_radix(&hex, 16).ok()?;
+            return char::from_u32(code).map(|c| (c, hex.len() + 3));
+        }
+        _ => return None,
+    };
+    Some((escaped, 1))
+}
+
+// a word followed by a char that can't continue it, like the ] in ]]
+fn word_id(word: &[char]) -> TokId {
+    lexmap_contains_value(&word_to_string(word)).unwrap_or(TokId::UNKNOWN)
 }
 
-fn word_to_string(word: &Vec<char>) -> String {
+fn word_to_string(word: &[char]) -> String {
     word.iter().collect()
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(source: &str) -> Vec<(TokId, String)> {
+        lexer_file(source, "test.nm").unwrap().into_iter().map(|lex| (lex.id, lex.rep)).collect()
+    }
+
+    fn unknown(rep: &str) -> (TokId, String) {
+        (TokId::UNKNOWN, rep.to_string())
+    }
+
+    #[test]
+    fn skips_comments_but_keeps_docs() {
+        assert_eq!(words("1 # 2 3\n4"), vec![unknown("1"), (TokId::LINEBREAK, "\n".into()), unknown("4")]);
+        assert_eq!(words("1 #[ 2 #[ 3 ]# 4 ]# 5"), vec![unknown("1"), unknown("5")]);
+        assert_eq!(words("1 #[ 2\n3 ]# 4"), vec![unknown("1"), unknown("4")]);
+        assert_eq!(words("1 # the end"), vec![unknown("1")]);
+        assert_eq!(
+            words("## adds one\nfun f"),
+            vec![(TokId::DOC, "adds one".into()), (TokId::LINEBREAK, "\n".into()), (TokId::FUNCTION, "fun".into()), unknown("f")],
+        );
+        let err = lexer_file("#[ 1 #[ 2 ]#", "test.nm").unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UNBALANCED);
+        assert!(err.message.contains("']#' is missing"), "{}", err.message);
+    }
+
+    fn string(rep: &str) -> (TokId, String) {
+        (TokId::STRING, rep.to_string())
+    }
+
+    #[test]
+    fn reads_escapes_raw_and_tripled_strings() {
+        assert_eq!(words(r#""a\tb\n\\\"\u{41}""#), vec![string("a\tb\n\\\"A")]);
+        assert_eq!(words(r"'a\tb\'"), vec![string(r"a\tb\")]);
+        assert_eq!(words("\"\"\"one\ntwo\"\"\" 1"), vec![string("one\ntwo"), unknown("1")]);
+        assert_eq!(words("'''a\\n\nb'''"), vec![string("a\\n\nb")]);
+        // a # inside a string is no comment
+        assert_eq!(words("\"# no\" 1"), vec![string("# no"), unknown("1")]);
+        let err = lexer_file(r#""\q""#, "test.nm").unwrap_err();
+        assert!(err.message.contains("unknown escape sequence"), "{}", err.message);
+        let err = lexer_file("\"one\ntwo\"", "test.nm").unwrap_err();
+        assert!(err.message.contains("strings over several lines use"), "{}", err.message);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::error::{ErrorKind, NumenError};
+use crate::head::{Function, Object, TokId, Value, GLOBAL};
+use crate::lexer::lexer_file;
+use crate::parser::parse_file;
+use crate::{get_std, read_file};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// default extension of numen files, added to imports that don't have one
+pub const EXTENSION: &str = "nm";
+
+// loads a file together with everything it imports into one function map
+pub fn link(filename: &str) -> Result<HashMap<String, Function>, NumenError> {
+    let mut function_map: HashMap<String, Function> = HashMap::new();
+    let global = Linker::new().link_file(canonical(filename)?, &mut function_map)?;
+    function_map.insert(
+        GLOBAL.to_string(),
+        Function {
+            arguments: vec![],
+            stack: global,
+            results: None,
+            doc: vec![],
+        },
+    );
+    Ok(function_map)
+}
+
+pub fn canonical(filename: &str) -> Result<PathBuf, NumenError> {
+    fs::canonicalize(filename).map_err(|_| NumenError::new(
+        ErrorKind::IO,
+        format!("LINKER: can't find the file {}", filename),
+    ))
+}
+
+// remembers which files are loaded, so each one is linked only once
+pub struct Linker {
+    loaded: HashSet<PathBuf>,
+    loading: Vec<PathBuf>,
+}
+
+impl Linker {
+    pub fn new() -> Linker {
+        Linker {
+            loaded: HashSet::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    // parses a single file, merges its functions into the map and returns its global
+    // section with every import replaced by the global section of the imported file
+    pub fn link_file(
+        &mut self,
+        path: PathBuf,
+        function_map: &mut HashMap<String, Function>,
+    ) -> Result<Vec<Object>, NumenError> {
+        if self.loading.contains(&path) {
+            let cycle: Vec<String> = self
+                .loading
+                .iter()
+                .skip_while(|p| **p != path)
+                .chain(std::iter::once(&path))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(NumenError::new(
+                ErrorKind::IMPORT,
+                format!("LINKER: import cycle {}", cycle.join(" -> ")),
+            ));
+        }
+        if self.loaded.contains(&path) {
+            // already imported, its global section has run once before
+            return Ok(vec![]);
+        }
+        self.loading.push(path.clone());
+
+        let file = read_file(&path.to_string_lossy().to_string())?;
+        let mut fmap = parse_file(lexer_file(&file, &path.to_string_lossy())?)?;
+        let global = fmap.remove(GLOBAL).ok_or_else(|| NumenError::new(
+            ErrorKind::UNDEFINED,
+            "LINKER: parser did not create a global function",
+        ))?;
+
+        for (name, fun) in fmap {
+            check_imports(&name, &fun)?;
+            if function_map.contains_key(&name) {
+                return Err(NumenError::new(
+                    ErrorKind::IMPORT,
+                    format!("LINKER: function {} in {} is already declared", name, path.display()),
+                ));
+            }
+            function_map.insert(name, fun);
+        }
+
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let linked = self.link_imports(global.stack, &dir, function_map)?;
+
+        self.loading.pop();
+        self.loaded.insert(path);
+        Ok(linked)
+    }
+
+    // replaces every import in a global section by the global section of the
+    // imported file, paths are relative to dir
+    pub fn link_imports(
+        &mut self,
+        global: Vec<Object>,
+        dir: &Path,
+        function_map: &mut HashMap<String, Function>,
+    ) -> Result<Vec<Object>, NumenError> {
+        let mut linked: Vec<Object> = Vec::new();
+        let mut iter = global.into_iter();
+        while let Some(tok) = iter.next() {
+            if tok.id != TokId::IMPORT {
+                linked.push(tok);
+                continue;
+            }
+            let target = iter.by_ref().find(|t| t.id != TokId::LINEBREAK).ok_or_else(|| NumenError::new(
+                ErrorKind::SYNTAX,
+                "LINKER: no file name to import",
+            ).at(&tok.pos))?;
+            let (TokId::STRING, Value::STR(target_name)) = (target.id, &target.rep) else {
+                return Err(NumenError::new(
+                    ErrorKind::SYNTAX,
+                    "LINKER: import expects the file name as a string",
+                ).at(&target.pos));
+            };
+            let target_path = resolve_import(dir, target_name).map_err(|err| err.at(&target.pos))?;
+            linked.extend(self.link_file(target_path, function_map).map_err(|err| err.at(&target.pos))?);
+        }
+        Ok(linked)
+    }
+}
+
+// imports are only resolved in the global section
+pub fn check_imports(name: &str, fun: &Function) -> Result<(), NumenError> {
+    match fun.stack.iter().find(|tok| tok.id == TokId::IMPORT) {
+        Some(tok) => Err(NumenError::new(
+            ErrorKind::IMPORT,
+            format!("LINKER: import inside the function {}, imports must be at the top level", name),
+        ).at(&tok.pos)),
+        None => Ok(()),
+    }
+}
+
+// standard library modules come first, other import paths are relative
+// to the directory of the importing file
+fn resolve_import(dir: &Path, name: &str) -> Result<PathBuf, NumenError> {
+    if get_std(name).is_some() {
+        return Ok(PathBuf::from(name.strip_suffix(".nm").unwrap_or(name)));
+    }
+    let mut path = dir.join(name);
+    if path.extension().is_none() {
+        path.set_extension(EXTENSION);
+    }
+    fs::canonicalize(&path).map_err(|_| NumenError::new(
+        ErrorKind::IMPORT,
+        format!("LINKER: can't find the file {}", path.display()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // writes the files into a directory of their own and returns the path of the first
+    fn files(test: &str, files: &[(&str, &str)]) -> String {
+        let dir = std::env::temp_dir().join(format!("numen-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, source) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, source).unwrap();
+        }
+        dir.join(files[0].0).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn reports_import_cycles() {
+        let main = files("cycle", &[
+            ("main.nm", "import \"a\""),
+            ("a.nm", "import \"b\"\nfun fa as end"),
+            ("b.nm", "import \"a.nm\"\nfun fb as end"),
+        ]);
+        let err = link(&main).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::IMPORT);
+        assert!(err.message.contains("import cycle"), "{}", err.message);
+        assert!(err.message.contains("a.nm -> ") && err.message.ends_with("a.nm"), "{}", err.message);
+    }
+
+    #[test]
+    fn loads_each_file_once() {
+        let main = files("once", &[
+            ("main.nm", "import \"a\" import \"b\" import \"shared\""),
+            ("a.nm", "import \"shared\"\nfun fa as end"),
+            ("b.nm", "import \"shared\"\nfun fb as end"),
+            ("shared.nm", "7 print\nfun twice as end"),
+        ]);
+        let fmap = link(&main).unwrap();
+        assert!(["fa", "fb", "twice"].iter().all(|name| fmap.contains_key(*name)));
+        // its global section runs once, where it is first imported
+        let sevens = fmap[GLOBAL].stack.iter().filter(|tok| tok.rep == Value::INT(7)).count();
+        assert_eq!(sevens, 1);
+    }
+
+    #[test]
+    fn resolves_the_std_library_before_files() {
+        let main = files("std", &[
+            ("main.nm", "import \"std/math\" import \"std/arrays.nm\""),
+            ("std/math.nm", "fun local as end"),
+        ]);
+        let fmap = link(&main).unwrap();
+        assert!(fmap.contains_key("gcd") && fmap.contains_key("range"));
+        assert!(!fmap.contains_key("local"));
+        let err = link(&files("missing", &[("main.nm", "import \"nowhere\"")])).unwrap_err();
+        assert!(err.message.contains("can't find the file"), "{}", err.message);
+    }
+}
@@ -1,29 +1,122 @@
+#![allow(clippy::upper_case_acronyms)]
 extern crate core;
 
 // root that all files share
 mod head;
+// errors, returned by every stage
+mod error;
 // lexer, parses indiviual lexemes
 mod lexer;
 // parser, parses function blocks and raw types
 mod interpreter;
 mod parser;
+// compiler, lowers functions into bytecode for the interpreter
+mod compiler;
+// bigint, arbitrary precision integers
+mod bigint;
+// linker, resolves imports and merges files into one program
+mod linker;
+// checker, finds stack and type errors before anything runs
+mod checker;
+// repl, runs code line by line
+mod repl;
 
-use crate::head::{Function, TokId};
+use crate::checker::{check, undefined};
+use crate::compiler::{compile, compile_function, struct_types};
+use crate::error::{ErrorKind, NumenError};
+use crate::head::{Function, TokId, GLOBAL};
 use crate::interpreter::interpret;
-use lexer::lexer_file;
-use parser::parse_file;
+use linker::link;
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::process::exit;
 
 pub fn main() {
     let args: Vec<String> = env::args().collect();
     let (_, filenames) = args.split_first().unwrap();
+    if filenames.is_empty() || filenames.iter().any(|arg| arg == "--repl") {
+        let filenames: Vec<String> = filenames.iter().filter(|arg| *arg != "--repl").cloned().collect();
+        if let Err(err) = repl::repl(&filenames) {
+            report(&err, None);
+            exit(1);
+        }
+        return;
+    }
+    if filenames.iter().any(|arg| arg == "--doc") {
+        for filename in filenames.iter().filter(|arg| *arg != "--doc") {
+            if let Err(err) = print_docs(filename) {
+                report(&err, None);
+                exit(1);
+            }
+        }
+        return;
+    }
+    if filenames.iter().any(|arg| arg == "--check") {
+        let mut failed = false;
+        for filename in filenames.iter().filter(|arg| *arg != "--check") {
+            for err in check_file(filename) {
+                report(&err, None);
+                failed = true;
+            }
+        }
+        if failed {
+            exit(1);
+        }
+        return;
+    }
     for filename in filenames {
-        run_file(filename);
+        if let Err(err) = run_file(filename) {
+            report(&err, None);
+            exit(1);
+        }
     }
 }
 
-//pub fn get_std(libname: &String) {}
+// prints the error with the line it happened on and a caret under the lexeme,
+// the source is read from the file of the error unless it is given
+pub fn report(err: &NumenError, source: Option<&str>) {
+    eprintln!("{}", err);
+    let Some(pos) = &err.pos else {
+        return;
+    };
+    let source = match source {
+        Some(source) => source.to_string(),
+        None => match read_source(&pos.file.to_string()) {
+            Ok(source) => source,
+            Err(_) => return,
+        },
+    };
+    let Some(line) = pos.line.checked_sub(1).and_then(|i| source.lines().nth(i)) else {
+        return;
+    };
+    let number = pos.line.to_string();
+    let pad = " ".repeat(number.len());
+    // keep tabs so the caret lines up with the source
+    let indent: String = line
+        .chars()
+        .take(pos.col.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    eprintln!("{} |", pad);
+    eprintln!("{} | {}", number, line);
+    eprintln!("{} | {}{}", pad, indent, "^".repeat(pos.len.max(1)));
+}
+
+// standard library, embedded into the binary
+const STD: [(&str, &str); 4] = [
+    ("std/math", include_str!("../std/math.nm")),
+    ("std/strings", include_str!("../std/strings.nm")),
+    ("std/arrays", include_str!("../std/arrays.nm")),
+    ("std/io", include_str!("../std/io.nm")),
+];
+
+pub fn get_std(libname: &str) -> Option<&'static str> {
+    let libname = libname.strip_suffix(".nm").unwrap_or(libname);
+    STD.iter()
+        .find(|(name, _)| *name == libname)
+        .map(|(_, contents)| *contents)
+}
 
 pub fn get_path(filename: &String) -> String {
     match env::current_exe() {
@@ -38,23 +131,28 @@ pub fn get_path(filename: &String) -> String {
     }
 }
 
-pub fn read_file(filename: &String) -> String {
+pub fn read_file(filename: &String) -> Result<String, NumenError> {
     if cfg!(any(debug_assertions)) { // IS COMPILED AS DEBUG MODE
         println!("MAIN: Reading file {}", filename);
     }
-    //TODO: check standard library and see if it is referenced
+    read_source(filename)
+}
+
+// the file or the std library, without the debug output so error reports can use it
+fn read_source(filename: &String) -> Result<String, NumenError> {
+    if let Some(contents) = get_std(filename) {
+        return Ok(contents.to_string());
+    }
     let filepath = get_path(filename);
 
-    let contents = fs::read_to_string(filepath).expect("Should have been able to read the file");
-    contents
+    fs::read_to_string(filepath).map_err(|err| NumenError::new(
+        ErrorKind::IO,
+        format!("MAIN: can't read the file {}: {}", filename, err),
+    ))
 }
 
-pub fn run_file(filename: &String) {
-    let file = read_file(filename);
-    //println!("{}", file);
-    let lexed = lexer_file(&file);
-    //dbg!(lexed.clone());
-    let fmap = parse_file(lexed);
+pub fn run_file(filename: &String) -> Result<(), NumenError> {
+    let fmap = link(&get_path(filename))?;
 
     for (name, fun) in &fmap {
         if cfg!(any(debug_assertions)) { // IS COMPILED AS DEBUG MODE
@@ -65,30 +163,86 @@ pub fn run_file(filename: &String) {
         println!("{:?}\n", fun);*/
     }
 
-    interpret(fmap);
+    // syntax errors come before what the checker finds
+    let code = compile(&fmap)?;
+    if let Some(err) = undefined(&fmap).into_iter().chain(check(&fmap)).next() {
+        return Err(err);
+    }
+    interpret(code)
 }
 
+// finds every problem of a file and its imports without running it,
+// a file that doesn't lex or parse only has the first one
+pub fn check_file(filename: &String) -> Vec<NumenError> {
+    let fmap = match link(&get_path(filename)) {
+        Ok(fmap) => fmap,
+        Err(err) => return vec![err],
+    };
+    let types = struct_types(&fmap);
+    let mut errors: Vec<NumenError> = undefined(&fmap);
+    // the checker only sees functions that compile, the others would only add noise
+    let mut compiled: HashMap<String, Function> = HashMap::new();
+    for (name, fun) in &fmap {
+        match compile_function(fun, &types) {
+            Ok(_) => {
+                compiled.insert(name.clone(), fun.clone());
+            }
+            Err(err) => errors.push(err),
+        }
+    }
+    errors.extend(check(&compiled));
+    errors.sort_by_key(|err| err.pos.as_ref().map(|pos| (pos.file.clone(), pos.line, pos.col)));
+    errors.dedup();
+    errors
+}
+
+// lists the functions of a file and of its imports together with their doc comments
+pub fn print_docs(filename: &String) -> Result<(), NumenError> {
+    let fmap = link(&get_path(filename))?;
+    let mut names: Vec<&String> = fmap.keys().filter(|name| *name != GLOBAL).collect();
+    names.sort();
+    for name in names {
+        let fun = &fmap[name];
+        let arguments: String = fun
+            .arguments
+            .iter()
+            .filter(|arg| arg.id != TokId::LINEBREAK)
+            .map(|arg| format!("{} ", arg.rep))
+            .collect();
+        let results: String = match &fun.results {
+            Some(results) => results.iter().fold("-- ".to_string(), |acc, result| format!("{}{} ", acc, result.rep)),
+            None => String::new(),
+        };
+        println!("fun {} {}{}as", name, arguments, results);
+        for line in &fun.doc {
+            println!("    {}", line);
+        }
+    }
+    Ok(())
+}
 
 fn print_function(name: &String, fun: &Function) {
     println!("\x1b[31;1m{}: \x1b[0m", name);
+    for line in &fun.doc {
+        println!("\x1b[90m## {}\x1b[0m", line);
+    }
     for item in &fun.stack {
         match item.id {
             TokId::WHILE | TokId::DO | TokId::IF | TokId::BLOCK
-            | TokId::ELSE | TokId::FUNCTION | TokId::IMPORT | TokId::END
-            | TokId::AS | TokId::RET | TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT
-            | TokId::ARRAY | TokId::LOOP => {
+            | TokId::ELSE | TokId::FUNCTION | TokId::STRUCT | TokId::IMPORT | TokId::END
+            | TokId::AS | TokId::EFFECT | TokId::RET | TokId::ASSIGNMENT | TokId::RETURNINGASSIGNMENT
+            | TokId::ARRAY | TokId::MAP | TokId::INSTANCE | TokId::QUOTE | TokId::LOOP | TokId::BREAK | TokId::CONTINUE => {
                 print!("\x1b[35m{} \x1b[0m", item.rep);
             }
             TokId::PLUS | TokId::MINUS | TokId::MULTIPLY | TokId::DIVIDE
-            | TokId::MOD | TokId::EQUALS | TokId::BIGGER | TokId::SMALLER
-            | TokId::BIGGEREQUALS | TokId::SMALLEREQUALS | TokId::IS => {
+            | TokId::MOD | TokId::POWER | TokId::EQUALS | TokId::BIGGER | TokId::SMALLER
+            | TokId::BIGGEREQUALS | TokId::SMALLEREQUALS | TokId::IS | TokId::NOTEQUALS
+            | TokId::AND | TokId::OR | TokId::XOR | TokId::NOT => {
                 print!("\x1b[31m{} \x1b[0m", item.rep);
             }
-            TokId::ARRAYBEGIN => {
-                print!("[ ");
-            }
-            TokId::ARRAYEND => {
-                print!("] ");
+            TokId::ARRAYBEGIN | TokId::ARRAYEND | TokId::MAPBEGIN | TokId::MAPEND
+            | TokId::QUOTEBEGIN | TokId::QUOTEEND => {
+                print!("{} ", item.rep);
             }
             TokId::STRING => {
                 print!("\x1b[32m\"{}\" \x1b[0m", item.rep);
@@ -96,19 +250,25 @@ fn print_function(name: &String, fun: &Function) {
             TokId::BOOLEAN => {
                 print!("\x1b[94m{} \x1b[0m", item.rep);
             }
-            TokId::INT | TokId::FLOAT => {
+            TokId::INT | TokId::BIGINT | TokId::FLOAT => {
                 print!("\x1b[33m{} \x1b[0m", item.rep);
             }
-            TokId::TINT | TokId::TFLOAT | TokId::TSTRING | TokId::TBOOL | TokId::TARRAY => {
+            TokId::TINT | TokId::TBIGINT | TokId::TFLOAT | TokId::TSTRING | TokId::TBOOL | TokId::TARRAY | TokId::TMAP | TokId::TQUOTE | TokId::TFUNCREF | TokId::TSTRUCT => {
                 print!("\x1b[95m{} \x1b[0m", item.rep);
             }
             TokId::LINEBREAK => {
                 print!("\n\t");
             }
+            TokId::DOC => {
+                print!("\x1b[90m## {} \x1b[0m", item.rep);
+            }
             TokId::UNKNOWN => {
                 print!("\x1b[34m{} \x1b[0m", item.rep);
             }
+            TokId::FUNCREF => {
+                print!("\x1b[34m&{} \x1b[0m", item.rep);
+            }
         }
     }
-    print!("\n")
+    println!()
 }
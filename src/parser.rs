@@ -1,91 +1,95 @@
-use crate::head::{Function, Lexeme, TokId, GLOBAL, Value, Object};
+use crate::bigint::BigInt;
+use crate::error::{ErrorKind, NumenError};
+use crate::head::{Function, Lexeme, Pos, StructDef, TokId, GLOBAL, TRUE, FALSE, Value, Object};
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::lexer::lexmap_contains_value;
 
-pub fn parse_file(mut lexed: Vec<Lexeme<String>>) -> HashMap<String, Function> {
+pub fn parse_file(mut lexed: Vec<Lexeme<String>>) -> Result<HashMap<String, Function>, NumenError> {
     //parse types
     parse_type(&mut lexed);
     // function hash map
     let mut function_map: HashMap<String, Function> = HashMap::new();
     // global func, representing global scope
     function_map.insert(
-        GLOBAL.clone().to_string(),
+        GLOBAL.to_string(),
         Function {
             arguments: vec![],
             stack: vec![],
+            results: None,
+            doc: vec![],
         },
     );
 
     let mut iter = lexed.iter();
     let mut fname: String = String::new();
-    let mut block_count = 0; // for stuff like if and while
-    let mut array_cont: Vec<Object> = Vec::new();
-    let mut inside_array = false;
+    let mut fpos = Pos::default(); // where the current function is declared
+    let mut open_blocks: Vec<Pos> = Vec::new(); // for stuff like if and while
+    let mut open_literals: Vec<&Lexeme<String>> = Vec::new(); // arrays, maps and quotations are compiled with the code around them
+    let mut doc: Vec<String> = Vec::new(); // ## lines waiting for the next function
     // parsing functions
     while let Some(lex) = iter.next() {
-        if lex.id == TokId::ARRAYBEGIN || inside_array {
-            if !inside_array {
-                inside_array = true;
-                array_cont.clear();
-                continue;
-            } else {
-                if lex.id == TokId::ARRAYEND {
-                    if !fname.is_empty() { // FUNCTION
-                        let funcref = function_map.get_mut(&fname).unwrap_or_else(||
-                            panic!("PARSER: the function {} is not declared!", fname)
-                        );
-                        funcref.stack.push(Object {
-                            id: TokId::ARRAY,
-                            rep: Value::ARR(array_cont.clone()),
-                        })
-                    } else { // GLOBAL
-                        if let Some(func) = function_map.get_mut(GLOBAL.clone()) {
-                            func.stack.push(Object {
-                                id: TokId::ARRAY,
-                                rep: Value::ARR(array_cont.clone()),
-                            });
-                        } else {
-                            panic!("PARSER: the function {} is not declared!", fname);
-                        }
-                    }
-                    inside_array = false;
-                    continue;
-                } else {
-                    array_cont.push(Object {
-                        id: lex.id,
-                        rep: Value::STR(lex.rep.clone()),
-                    });
-                    continue;
-                }
+        if lex.id == TokId::DOC {
+            if fname.is_empty() && open_literals.is_empty() {
+                doc.push(lex.rep.clone());
             }
+            continue;
+        }
+        // doc comments only belong to a function or struct that follows them directly
+        if !matches!(lex.id, TokId::FUNCTION | TokId::STRUCT | TokId::LINEBREAK) {
+            doc.clear();
+        }
+        if matches!(lex.id, TokId::ARRAYBEGIN | TokId::MAPBEGIN | TokId::QUOTEBEGIN) {
+            open_literals.push(lex);
+        } else if matches!(lex.id, TokId::ARRAYEND | TokId::MAPEND | TokId::QUOTEEND) && open_literals.pop().is_none() {
+            let (open, what) = match lex.id {
+                TokId::ARRAYEND => ("[", "array"),
+                TokId::MAPEND => ("{", "map"),
+                _ => ("(", "quotation"),
+            };
+            return Err(NumenError::new(
+                ErrorKind::UNBALANCED,
+                format!("PARSER: '{}' is missing for the {}", open, what),
+            ).at(&lex.pos));
         }
 
         //inside the function
         if !fname.is_empty() {
-            let funcref = function_map.get_mut(&fname).unwrap_or_else(||
-                panic!("PARSER: the function {} is not declared!", fname)
-            );
+            let funcref = function_map.get_mut(&fname).ok_or_else(|| NumenError::new(
+                ErrorKind::UNDEFINED,
+                format!("PARSER: the function {} is not declared!", fname),
+            ))?;
 
             match lex.id {
                 TokId::FUNCTION => {
                     //other than global, one should not declare functions inside functions
-                    panic!(
-                        "PARSER: can't declare a function inside one\n {} inside {}",
-                        fname,
-                        iter.next().unwrap().rep
-                    )
+                    return Err(NumenError::new(
+                        ErrorKind::SYNTAX,
+                        format!(
+                            "PARSER: can't declare a function inside one\n {} inside {}",
+                            iter.next().map(|l| l.rep.as_str()).unwrap_or(""),
+                            fname
+                        ),
+                    ).at(&lex.pos));
+                }
+                TokId::STRUCT => {
+                    return Err(NumenError::new(
+                        ErrorKind::SYNTAX,
+                        format!("PARSER: can't declare a struct inside the function {}", fname),
+                    ).at(&lex.pos));
                 }
                 TokId::END => {
-                    if block_count > 0 {
-                        block_count -= 1;
+                    if open_blocks.pop().is_some() {
                         funcref.stack.push(lex2obj(lex.clone()));
+                    } else if let Some(open) = open_literals.pop() {
+                        return Err(unclosed(open));
                     } else {
                         fname.clear();
                     }
                 }
                 TokId::WHILE | TokId::IF | TokId::BLOCK | TokId::LOOP => { //BLOCK CHECK
-                    block_count += 1;
+                    open_blocks.push(lex.pos.clone());
                     funcref.stack.push(lex2obj(lex.clone()));
                 }
                 _ => {
@@ -97,83 +101,349 @@ pub fn parse_file(mut lexed: Vec<Lexeme<String>>) -> HashMap<String, Function> {
         // global
         match lex.id {
             TokId::FUNCTION => {
-                let nameref = iter.next().unwrap();
+                if !open_blocks.is_empty() || !open_literals.is_empty() {
+                    return Err(NumenError::new(
+                        ErrorKind::SYNTAX,
+                        "PARSER: can't declare a function inside a block",
+                    ).at(&lex.pos));
+                }
+                let nameref = iter.next().ok_or_else(|| NumenError::new(
+                    ErrorKind::SYNTAX,
+                    "PARSER: function has no name",
+                ).at(&lex.pos))?;
                 if nameref.id != TokId::UNKNOWN {
-                    panic!(
-                        "PARSER: function name {} is alredy used as {:?}",
-                        nameref.rep, nameref.id
-                    )
+                    return Err(NumenError::new(
+                        ErrorKind::SYNTAX,
+                        format!("PARSER: function name {} is alredy used as {:?}", nameref.rep, nameref.id),
+                    ).at(&nameref.pos));
                 }
                 fname = nameref.rep.clone();
+                fpos = nameref.pos.clone();
 
-                validate_name(&fname);
+                validate_name(&fname).map_err(|err| err.at(&fpos))?;
 
                 // handle function parameters
                 let mut new_func = Function {
                     stack: vec![],
                     arguments: vec![],
+                    results: None,
+                    doc: std::mem::take(&mut doc),
                 };
-                let mut param = iter.next().unwrap();
-                if param.id != TokId::AS {
-                    while param.id != TokId::AS {
-                        new_func.arguments.push(lex2obj(param.clone()));
-                        param = iter.next().unwrap();
+                let missing_as = || NumenError::new(
+                    ErrorKind::UNBALANCED,
+                    format!("PARSER: 'as' is missing for the function {}", fname),
+                ).at(&fpos);
+                let mut param = iter.next().ok_or_else(missing_as)?;
+                while param.id != TokId::AS {
+                    match (param.id, &mut new_func.results) {
+                        (TokId::LINEBREAK, _) => {}
+                        (TokId::EFFECT, None) => new_func.results = Some(vec![]),
+                        (TokId::EFFECT, Some(_)) => {
+                            return Err(NumenError::new(
+                                ErrorKind::SYNTAX,
+                                format!("PARSER: the function {} has more than one --", fname),
+                            ).at(&param.pos));
+                        }
+                        (_, Some(results)) => {
+                            validate_result(param, results).map_err(|err| err.at(&param.pos))?;
+                            results.push(lex2obj(param.clone()));
+                        }
+                        (_, None) => new_func.arguments.push(lex2obj(param.clone())),
                     }
+                    param = iter.next().ok_or_else(missing_as)?;
                 }
 
                 function_map.insert(fname.clone(), new_func);
                 continue;
                 /*parse_function(function_map, iter.as_slice(), &nfname)*/
             }
+            TokId::STRUCT => {
+                if !open_blocks.is_empty() || !open_literals.is_empty() {
+                    return Err(NumenError::new(
+                        ErrorKind::SYNTAX,
+                        "PARSER: can't declare a struct inside a block",
+                    ).at(&lex.pos));
+                }
+                let nameref = iter.next().ok_or_else(|| NumenError::new(
+                    ErrorKind::SYNTAX,
+                    "PARSER: struct has no name",
+                ).at(&lex.pos))?;
+                validate_name(&nameref.rep).map_err(|err| err.at(&nameref.pos))?;
+                let mut fields: Vec<&Lexeme<String>> = Vec::new();
+                loop {
+                    let field = iter.next().ok_or_else(|| NumenError::new(
+                        ErrorKind::UNBALANCED,
+                        format!("PARSER: 'end' is missing for the struct {}", nameref.rep),
+                    ).at(&nameref.pos))?;
+                    match field.id {
+                        TokId::END => break,
+                        TokId::LINEBREAK | TokId::DOC => {}
+                        _ => {
+                            validate_name(&field.rep).map_err(|err| err.at(&field.pos))?;
+                            // Name.new is the constructor
+                            if field.rep == "new" {
+                                return Err(NumenError::new(
+                                    ErrorKind::SYNTAX,
+                                    format!("PARSER: the struct {} can't have a field named new", nameref.rep),
+                                ).at(&field.pos));
+                            }
+                            if fields.iter().any(|f| f.rep == field.rep) {
+                                return Err(NumenError::new(
+                                    ErrorKind::SYNTAX,
+                                    format!("PARSER: the struct {} already has the field {}", nameref.rep, field.rep),
+                                ).at(&field.pos));
+                            }
+                            fields.push(field);
+                        }
+                    }
+                }
+                declare_struct(nameref, &fields, std::mem::take(&mut doc), &mut function_map);
+            }
             TokId::END => {
-                panic!("PARSER: Two many ends!");
+                if open_blocks.pop().is_none() {
+                    return Err(NumenError::new(ErrorKind::UNBALANCED, "PARSER: Two many ends!").at(&lex.pos));
+                }
+                if let Some(func) = function_map.get_mut(GLOBAL) {
+                    func.stack.push(lex2obj(lex.clone()));
+                }
+            }
+            TokId::WHILE | TokId::IF | TokId::BLOCK | TokId::LOOP => { //BLOCK CHECK
+                open_blocks.push(lex.pos.clone());
+                if let Some(func) = function_map.get_mut(GLOBAL) {
+                    func.stack.push(lex2obj(lex.clone()));
+                }
             }
             _ => {
-                if let Some(func) = function_map.get_mut(GLOBAL.clone()) {
+                if let Some(func) = function_map.get_mut(GLOBAL) {
                     func.stack.push(lex2obj(lex.clone()));
                 } else {
-                    panic!("PARSER: the function {} is not declared!", fname);
+                    return Err(NumenError::new(
+                        ErrorKind::UNDEFINED,
+                        format!("PARSER: the function {} is not declared!", fname),
+                    ));
                 }
             }
         }
     }
+    if let Some(open) = open_literals.pop() {
+        return Err(unclosed(open));
+    }
+    if !fname.is_empty() {
+        return Err(NumenError::new(
+            ErrorKind::UNBALANCED,
+            format!("PARSER: 'end' is missing for the function {}", fname),
+        ).at(&fpos));
+    }
+    if let Some(pos) = open_blocks.pop() {
+        return Err(NumenError::new(ErrorKind::UNBALANCED, "PARSER: 'end' is missing for the block").at(&pos));
+    }
 
-    function_map
+    Ok(function_map)
 } // end of parse
 
+// a struct is a set of words, Name pushes its type, Name.new takes the fields
+// with the last one on top, Name.field gets a field and Name.field! sets it
+fn declare_struct(
+    name: &Lexeme<String>,
+    fields: &[&Lexeme<String>],
+    doc: Vec<String>,
+    function_map: &mut HashMap<String, Function>,
+) {
+    let def = Rc::new(StructDef {
+        name: name.rep.as_str().into(),
+        fields: fields.iter().map(|field| field.rep.as_str().into()).collect(),
+    });
+    let obj = |id: TokId, rep: Value, pos: &Pos| Object { id, rep, pos: pos.clone() };
+    let word = |text: &str, pos: &Pos| obj(TokId::UNKNOWN, Value::STR(text.into()), pos);
+    let typ = obj(TokId::TSTRUCT, Value::DEF(def.clone()), &name.pos);
+    let ret = obj(TokId::RET, Value::STR("ret".into()), &name.pos);
+
+    function_map.insert(name.rep.clone(), Function {
+        arguments: vec![],
+        stack: vec![typ.clone(), ret.clone()],
+        results: None,
+        doc,
+    });
+
+    // the arguments come from the top of the stack, so the last field first
+    let args: Vec<String> = (0..fields.len()).map(|i| format!("_{}", i)).collect();
+    let mut stack: Vec<Object> = args.iter().map(|arg| word(arg, &name.pos)).collect();
+    stack.extend([typ.clone(), word("new", &name.pos), ret.clone()]);
+    function_map.insert(format!("{}.new", name.rep), Function {
+        arguments: args.iter().rev().map(|arg| word(arg, &name.pos)).collect(),
+        stack,
+        results: Some(vec![typ.clone()]),
+        doc: vec![format!("makes a {} out of {}", name.rep, def.fields.join(" "))],
+    });
+
+    for field in fields {
+        let key = obj(TokId::STRING, Value::STR(field.rep.as_str().into()), &field.pos);
+        function_map.insert(format!("{}.{}", name.rep, field.rep), Function {
+            arguments: vec![typ.clone()],
+            stack: vec![key.clone(), word("get", &field.pos), ret.clone()],
+            results: Some(vec![word(&field.rep, &field.pos)]),
+            doc: vec![format!("the {} of a {}", field.rep, name.rep)],
+        });
+        function_map.insert(format!("{}.{}!", name.rep, field.rep), Function {
+            arguments: vec![word("_value", &field.pos), typ.clone()],
+            stack: vec![key, word("_value", &field.pos), word("set", &field.pos), ret.clone()],
+            results: Some(vec![typ.clone()]),
+            doc: vec![format!("sets the {} of a {}", field.rep, name.rep)],
+        });
+    }
+}
+
+// an array, map or quotation literal that is never closed
+fn unclosed(open: &Lexeme<String>) -> NumenError {
+    let (close, what) = match open.id {
+        TokId::ARRAYBEGIN => ("]", "array"),
+        TokId::MAPBEGIN => ("}", "map"),
+        _ => (")", "quotation"),
+    };
+    NumenError::new(
+        ErrorKind::UNBALANCED,
+        format!("PARSER: '{}' is missing for the {}", close, what),
+    ).at(&open.pos)
+}
+
 fn parse_type(lexed: &mut Vec<Lexeme<String>>) {
     for lex in lexed {
         if lex.id == TokId::UNKNOWN {
-            if lex.rep == "true" {
+            if lex.rep == TRUE || lex.rep == FALSE {
                 lex.id = TokId::BOOLEAN;
-            } else if lex.rep == "false" {
-                lex.id = TokId::BOOLEAN;
-            } else if let Ok(_) = lex.rep.parse::<i32>() {
-                lex.id = TokId::INT;
-            } else if let Ok(_) = lex.rep.parse::<f64>() {
+            } else if let Some(int) = parse_int(&lex.rep) {
+                lex.id = match int {
+                    Value::INT(_) => TokId::INT,
+                    // too big for an int
+                    _ => TokId::BIGINT,
+                };
+            } else if lex.rep.parse::<f64>().is_ok()
+                && (lex.rep.contains(|c: char| c.is_ascii_digit()) || matches!(lex.rep.as_str(), "nan" | "inf" | "-inf"))
+            {
                 lex.id = TokId::FLOAT;
+            } else if lex.rep.strip_prefix('&').is_some_and(|name| validate_name(&name.to_string()).is_ok()) {
+                lex.id = TokId::FUNCREF;
             }
         }
     }
 }
 
-fn validate_name(name: &String) {
+// an int literal, 0x, 0b and 0o start hex, binary and octal digits and _ can
+// split the digits up, one too big for an int is a bigint
+fn parse_int(rep: &str) -> Option<Value> {
+    let (sign, rest) = match rep.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", rep.strip_prefix('+').unwrap_or(rep)),
+    };
+    let (radix, digits) = match rest.get(..2) {
+        Some("0x" | "0X") => (16, &rest[2..]),
+        Some("0b" | "0B") => (2, &rest[2..]),
+        Some("0o" | "0O") => (8, &rest[2..]),
+        _ => (10, rest),
+    };
+    // _ only goes between digits
+    if digits.is_empty()
+        || digits.starts_with('_')
+        || digits.ends_with('_')
+        || digits.contains("__")
+        || !digits.chars().all(|c| c == '_' || c.is_digit(radix))
+    {
+        return None;
+    }
+    let text = format!("{}{}", sign, digits.replace('_', ""));
+    match i64::from_str_radix(&text, radix) {
+        Ok(int) => Some(Value::INT(int)),
+        Err(_) => BigInt::parse_radix(&text, radix).map(Value::BIG),
+    }
+}
+
+// a result is a type or a name for a value of any type, ret gives back
+// only the top of the stack so there is one at most
+fn validate_result(result: &Lexeme<String>, results: &[Object]) -> Result<(), NumenError> {
+    if !results.is_empty() {
+        return Err(NumenError::new(
+            ErrorKind::SYNTAX,
+            "PARSER: a function returns one value at most, ret only gives back the top of the stack",
+        ));
+    }
+    match result.id {
+        TokId::TINT | TokId::TBIGINT | TokId::TFLOAT | TokId::TSTRING | TokId::TBOOL | TokId::TARRAY
+        | TokId::TMAP | TokId::TQUOTE | TokId::TFUNCREF => Ok(()),
+        _ => validate_name(&result.rep),
+    }
+}
+
+fn validate_name(name: &String) -> Result<(), NumenError> {
     if lexmap_contains_value(name.as_str()).is_some() {
-        panic!("PARSER: \"{}\" name can't be a reserved word", name)
+        return Err(NumenError::new(
+            ErrorKind::SYNTAX,
+            format!("PARSER: \"{}\" name can't be a reserved word", name),
+        ));
     }
     for (i, char) in name.chars().enumerate() {
         if i == 0 && (!char.is_alphabetic() && char != '_') {
-            panic!("PARSER: \"{}\" first char of name is not valid", name)
+            return Err(NumenError::new(
+                ErrorKind::SYNTAX,
+                format!("PARSER: \"{}\" first char of name is not valid", name),
+            ));
         }
         if !char.is_alphabetic() && char != '_' && !char.is_numeric() {
-            panic!("PARSER: \"{}\" char at {} is not valid", name, i)
+            return Err(NumenError::new(
+                ErrorKind::SYNTAX,
+                format!("PARSER: \"{}\" char at {} is not valid", name, i),
+            ));
         }
     }
+    Ok(())
 }
 
+// literals get their native value, parse_type has already checked that they parse
 fn lex2obj(lex: Lexeme<String>) -> Object {
+    let rep = match lex.id {
+        TokId::INT | TokId::BIGINT => parse_int(&lex.rep),
+        TokId::FLOAT => lex.rep.parse().map(Value::FLOAT).ok(),
+        TokId::BOOLEAN => Some(Value::BOOL(lex.rep == TRUE)),
+        TokId::FUNCREF => lex.rep.strip_prefix('&').map(|name| Value::STR(name.into())),
+        _ => None,
+    };
     Object {
         id: lex.id,
-        rep: Value::STR(lex.rep),
+        rep: rep.unwrap_or_else(|| Value::STR(lex.rep.into())),
+        pos: lex.pos,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_ints_in_every_radix() {
+        assert_eq!(parse_int("42"), Some(Value::INT(42)));
+        assert_eq!(parse_int("-42"), Some(Value::INT(-42)));
+        assert_eq!(parse_int("+42"), Some(Value::INT(42)));
+        assert_eq!(parse_int("0xFF"), Some(Value::INT(255)));
+        assert_eq!(parse_int("0Xff"), Some(Value::INT(255)));
+        assert_eq!(parse_int("-0x10"), Some(Value::INT(-16)));
+        assert_eq!(parse_int("0b1010"), Some(Value::INT(10)));
+        assert_eq!(parse_int("0o17"), Some(Value::INT(15)));
+        assert_eq!(parse_int("1_000_000"), Some(Value::INT(1_000_000)));
+        assert_eq!(parse_int("0b1111_0000"), Some(Value::INT(0xF0)));
+    }
+
+    #[test]
+    fn reads_the_edges_of_an_int() {
+        assert_eq!(parse_int("9223372036854775807"), Some(Value::INT(i64::MAX)));
+        assert_eq!(parse_int("-9223372036854775808"), Some(Value::INT(i64::MIN)));
+        assert_eq!(parse_int("-0x8000000000000000"), Some(Value::INT(i64::MIN)));
+        assert_eq!(parse_int("9223372036854775808"), Some(Value::BIG(&BigInt::from(i64::MAX) + &BigInt::from(1))));
+        assert_eq!(parse_int("0xFFFFFFFFFFFFFFFF"), BigInt::parse_radix("18446744073709551615", 10).map(Value::BIG));
+    }
+
+    #[test]
+    fn rejects_bad_digits_and_underscores() {
+        for rep in ["", "-", "0x", "0b102", "0o8", "12a", "_1", "1_", "1__0", "0x_F", "--1", "-+1"] {
+            assert_eq!(parse_int(rep), None, "{}", rep);
+        }
     }
 }
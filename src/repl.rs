@@ -0,0 +1,101 @@
+use crate::checker::check_function;
+use crate::compiler::{compile_function, struct_types};
+use crate::error::{ErrorKind, NumenError};
+use crate::head::{Lexeme, TokId, GLOBAL};
+use crate::interpreter::{array2string, Session};
+use crate::lexer::lexer_file;
+use crate::linker::{canonical, check_imports, Linker};
+use crate::parser::parse_file;
+use crate::{get_path, report};
+use std::env;
+use std::io::{self, BufRead, Write};
+
+// file name used for the positions of code typed into the repl
+pub const REPL: &str = "<repl>";
+
+// reads code line by line and runs it, keeping functions, globals and the stack
+// alive in between, files are loaded into the session before the first line
+pub fn repl(filenames: &[String]) -> Result<(), NumenError> {
+    let mut session = Session::new();
+    let mut linker = Linker::new();
+    for filename in filenames {
+        let global = linker.link_file(canonical(&get_path(filename))?, &mut session.function_map)?;
+        session.run(global)?;
+    }
+
+    let stdin = io::stdin();
+    let mut buffer = String::new();
+    loop {
+        print!("{}", if buffer.is_empty() { "> " } else { "... " });
+        io::stdout().flush().map_err(io_error)?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(io_error)? == 0 {
+            println!();
+            return Ok(());
+        }
+        buffer.push_str(&line);
+
+        let lexed = match lexer_file(&buffer, REPL) {
+            Ok(lexed) => lexed,
+            // a block comment can go on for several lines
+            Err(err) if err.kind == ErrorKind::UNBALANCED => continue,
+            Err(err) => {
+                report(&err, Some(&buffer));
+                buffer.clear();
+                continue;
+            }
+        };
+        // fun, blocks and arrays can go on for several lines
+        if is_open(&lexed) {
+            continue;
+        }
+        match eval(&mut session, &mut linker, lexed) {
+            Ok(()) => println!("{}", array2string(session.stack.clone())),
+            Err(err) => report(&err, Some(&buffer)),
+        }
+        buffer.clear();
+    }
+}
+
+fn eval(
+    session: &mut Session,
+    linker: &mut Linker,
+    lexed: Vec<Lexeme<String>>,
+) -> Result<(), NumenError> {
+    let mut fmap = parse_file(lexed)?;
+    let global = fmap.remove(GLOBAL).ok_or_else(|| NumenError::new(
+        ErrorKind::UNDEFINED,
+        "REPL: parser did not create a global function",
+    ))?;
+    // functions defined again replace the old ones
+    let globals = session.global_heap.keys().cloned().collect();
+    for (name, fun) in fmap {
+        check_imports(&name, &fun)?;
+        compile_function(&fun, &struct_types(&session.function_map))?;
+        if let Some(err) = check_function(&name, &fun, &session.function_map, &globals).into_iter().next() {
+            return Err(err);
+        }
+        session.function_map.insert(name, fun);
+    }
+    let dir = env::current_dir().map_err(io_error)?;
+    let code = linker.link_imports(global.stack, &dir, &mut session.function_map)?;
+    session.run(code)
+}
+
+// true while a fun, block or array is missing its end
+fn is_open(lexed: &[Lexeme<String>]) -> bool {
+    let mut depth = 0;
+    for lex in lexed {
+        match lex.id {
+            TokId::FUNCTION | TokId::STRUCT | TokId::WHILE | TokId::IF | TokId::BLOCK | TokId::LOOP
+            | TokId::ARRAYBEGIN | TokId::MAPBEGIN | TokId::QUOTEBEGIN => depth += 1,
+            TokId::END | TokId::ARRAYEND | TokId::MAPEND | TokId::QUOTEEND => depth -= 1,
+            _ => {}
+        }
+    }
+    depth > 0
+}
+
+fn io_error(err: io::Error) -> NumenError {
+    NumenError::new(ErrorKind::IO, format!("REPL: {}", err))
+}